@@ -1,330 +1,5922 @@
-use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
 use std::env;
-use std::num::ParseIntError;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
+use tokio::time::{sleep, timeout};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
 
 use serenity::async_trait;
-use serenity::client::bridge::gateway::ShardManager;
-use serenity::framework::standard::macros::{command, group};
-use serenity::framework::standard::{ArgError, Args, CommandResult};
-use serenity::framework::StandardFramework;
-use serenity::http::Http;
+use serenity::client::bridge::gateway::event::ShardStageUpdateEvent;
+use serenity::client::bridge::gateway::{ShardId, ShardManager};
+use serenity::framework::standard::macros::{command, group, help, hook};
+use serenity::framework::standard::{
+    Args, CommandGroup, CommandResult, Delimiter, DispatchError, HelpOptions,
+};
+use serenity::framework::{Framework, StandardFramework};
+use serenity::gateway::{ConnectionStage, GatewayError};
+use serenity::http::{Http, HttpError, StatusCode};
+use serenity::model::application::command::{Command, CommandOptionType};
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::{Interaction, InteractionResponseType};
+use serenity::model::error::Error as ModelError;
+use serenity::model::event::{MessageUpdateEvent, ResumedEvent};
 use serenity::model::gateway::{GatewayIntents, Ready};
-use serenity::model::prelude::Message;
-use serenity::model::prelude::ReactionType;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::prelude::{Channel, Member, Message, Reaction};
+use serenity::model::prelude::{ReactionConversionError, ReactionType};
 use serenity::model::user::User;
+use serenity::model::Timestamp;
 use serenity::prelude::*;
 
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+use serde::{Deserialize, Serialize};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till},
+    character::complete::{char, digit1, not_line_ending, satisfy},
+    combinator::{map_opt, map_res, recognize},
+    sequence::terminated,
+    IResult,
+};
+
+/// Environment variables settings are read from: the process environment,
+/// over `.env` when it's re-read by `*reload`.
+type EnvVars = HashMap<String, String>;
+
+fn env_var(vars: &EnvVars, var: &str) -> Result<String, env::VarError> {
+    vars.get(var).cloned().ok_or(env::VarError::NotPresent)
+}
+
+fn parse_env<T: FromStr>(vars: &EnvVars, var: &str, default: T) -> Result<T, String> {
+    match env_var(vars, var) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Could not parse {var}, got {value:?}")),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Settings from `CONFIG_FILE` (default `config.json`). Every field is
+/// optional and an environment variable of the same name in upper case, e.g.
+/// `MAX_ID` for `max_id`, overrides it.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    command_prefix: String,
+    sriracha_bot_id: u64,
+    ohsheet_bot_id: u64,
+    lc_bot_id: u64,
+    fort_checker_bot_id: u64,
+    /// Per group, e.g. `{"lc": {"stage": 3, "move_target": 4}}`.
+    stages: HashMap<String, StageFile>,
+    /// Address stages by sheet column letter, e.g. `C#12` instead of `3#12`.
+    column_letters: bool,
+    /// Column letter per group, e.g. `{"lc": "C"}`, overridden by `<GROUP>_COLUMN`.
+    columns: HashMap<String, String>,
+    author_lookup_delay_ms: u64,
+    author_lookup_retries: u32,
+    confirm_deletes: bool,
+    sauce_keyword: String,
+    status_id: u32,
+    send_timeout_ms: u64,
+    send_interval_ms: u64,
+    command_aliases: HashMap<String, String>,
+    /// Roles (ids or names) needed per command, action or group.
+    command_roles: HashMap<String, Vec<String>>,
+    allowed_channel_ids: Vec<u64>,
+    error_channel_id: Option<u64>,
+    max_id: u32,
+    list_all_command: String,
+    /// Allowed moves as `[from, to]` pairs.
+    move_transitions: Vec<(u32, u32)>,
+    list_page_size: usize,
+    confirm_emoji: String,
+    cancel_emoji: String,
+    /// Reaction that moves the item on a tracked embed; empty disables it.
+    advance_emoji: String,
+    /// Replaces `languages.json` unless `LANGUAGES_FILE` is set.
+    languages: Option<HashMap<String, String>>,
+    /// Custom emoji per language code, overridden by `<CODE>_EMOJI`.
+    language_emojis: HashMap<String, String>,
+    sauce_cooldown_secs: u64,
+    keepalive_interval_secs: u64,
+    health_port: u16,
+    track_presences: bool,
+    connect_retries: u32,
+    shutdown_grace_secs: u64,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StageFile {
+    stage: Option<u32>,
+    move_target: Option<u32>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            command_prefix: "*".to_string(),
+            sriracha_bot_id: 607661949194469376,
+            ohsheet_bot_id: 640402425395675178,
+            lc_bot_id: 661826254215053324,
+            fort_checker_bot_id: 1014282115086565486,
+            stages: HashMap::new(),
+            column_letters: false,
+            columns: HashMap::new(),
+            author_lookup_delay_ms: 3000,
+            author_lookup_retries: 2,
+            confirm_deletes: true,
+            sauce_keyword: "sauce".to_string(),
+            status_id: 1,
+            send_timeout_ms: 10000,
+            send_interval_ms: 250,
+            command_aliases: HashMap::new(),
+            command_roles: HashMap::new(),
+            allowed_channel_ids: Vec::new(),
+            error_channel_id: None,
+            max_id: 999,
+            list_all_command: "{stage}".to_string(),
+            move_transitions: Vec::new(),
+            list_page_size: 10,
+            confirm_emoji: CONFIRM_EMOJI.to_string(),
+            cancel_emoji: CANCEL_EMOJI.to_string(),
+            advance_emoji: String::new(),
+            languages: None,
+            language_emojis: HashMap::new(),
+            sauce_cooldown_secs: 2,
+            keepalive_interval_secs: 300,
+            health_port: 0,
+            track_presences: false,
+            connect_retries: 3,
+            shutdown_grace_secs: 10,
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Reads `CONFIG_FILE`, using the defaults when it doesn't exist.
+    fn load(vars: &EnvVars) -> Result<Self, String> {
+        let path = env_var(vars, "CONFIG_FILE").unwrap_or_else(|_| "config.json".to_string());
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                info!("Loading config from {}", path);
+                serde_json::from_str(&contents)
+                    .map_err(|why| format!("Could not parse {path}: {why}"))
+            }
+            Err(why) if why.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(why) => Err(format!("Could not read {path}: {why}")),
+        }
+    }
+}
+
+/// Parses `COMMAND_ALIASES`, e.g. `rm=qc delete, ls=lc`, into alias -> command.
+fn parse_aliases(input: &str) -> Result<HashMap<String, String>, String> {
+    let mut aliases = HashMap::new();
+    for entry in input.split(',').filter(|entry| !entry.trim().is_empty()) {
+        match entry.split_once('=') {
+            Some((alias, command)) if !alias.trim().is_empty() && !command.trim().is_empty() => {
+                aliases.insert(alias.trim().to_lowercase(), command.trim().to_string());
+            }
+            _ => {
+                return Err(format!(
+                    "Could not parse COMMAND_ALIASES entry {entry:?}, expected alias=command"
+                ))
+            }
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Parses `COMMAND_ROLES`, e.g. `delete=Staff|1234, lc_move=Staff`, into
+/// command -> roles. Keys are command names like `lc_delete`, actions shared
+/// by every stage like `delete` or `move_range`, or whole groups like `lc`.
+fn parse_command_roles(input: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut command_roles = HashMap::new();
+    for entry in input.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let parsed = entry.split_once('=').and_then(|(command, roles)| {
+            let roles: Vec<_> = roles
+                .split('|')
+                .map(str::trim)
+                .filter(|role| !role.is_empty())
+                .map(str::to_string)
+                .collect();
+            (!command.trim().is_empty() && !roles.is_empty())
+                .then(|| (command.trim().to_lowercase(), roles))
+        });
+        match parsed {
+            Some((command, roles)) => {
+                command_roles.insert(command, roles);
+            }
+            None => {
+                return Err(format!(
+                    "Could not parse COMMAND_ROLES entry {entry:?}, expected command=role|role"
+                ))
+            }
+        }
+    }
+
+    Ok(command_roles)
+}
+
+/// Parses a comma separated list of channel ids, e.g. `ALLOWED_CHANNEL_IDS`,
+/// falling back to `default` when `var` isn't set.
+fn parse_channel_ids(
+    vars: &EnvVars,
+    var: &str,
+    default: &[u64],
+) -> Result<HashSet<ChannelId>, String> {
+    let input = match env_var(vars, var) {
+        Ok(input) => input,
+        Err(_) => return Ok(default.iter().copied().map(ChannelId).collect()),
+    };
+
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse()
+                .map(ChannelId)
+                .map_err(|_| format!("Could not parse {var}, got {id:?}"))
+        })
+        .collect()
+}
+
+/// Parses `MOVE_TRANSITIONS`, e.g. `1>2, 2>3, 3>4, 2>1`, into the allowed
+/// (from, to) stage moves.
+fn parse_transitions(input: &str) -> Result<HashSet<(u32, u32)>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once('>')
+                .and_then(|(from, to)| Some((from.trim().parse().ok()?, to.trim().parse().ok()?)))
+                .ok_or_else(|| {
+                    format!("Could not parse MOVE_TRANSITIONS entry {entry:?}, expected from>to")
+                })
+        })
+        .collect()
+}
+
+/// Loads the language code -> emoji map from `LANGUAGES_FILE` (default
+/// `languages.json`), e.g. `{"en": "🇺🇸", "de": "<:de:1234>"}`. The config
+/// file's `languages` take the place of the default file, and the built-in
+/// languages are used when neither exists.
+fn load_languages(
+    vars: &EnvVars,
+    configured: Option<&HashMap<String, String>>,
+) -> Result<HashMap<String, ReactionType>, String> {
+    let (path, configured) = match env_var(vars, "LANGUAGES_FILE") {
+        Ok(path) => (path, None),
+        Err(_) if configured.is_some() => ("the config file".to_string(), configured),
+        Err(_) => ("languages.json".to_string(), None),
+    };
+    let emojis: HashMap<String, String> = match configured {
+        Some(configured) => configured.clone(),
+        None => match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|why| format!("Could not parse {path}: {why}"))?,
+            Err(why) if why.kind() == ErrorKind::NotFound => LANGUAGES
+                .into_iter()
+                .map(|(code, flag)| (code.to_string(), flag.to_string()))
+                .collect(),
+            Err(why) => return Err(format!("Could not read {path}: {why}")),
+        },
+    };
+
+    emojis
+        .into_iter()
+        .map(|(code, emoji)| match ReactionType::from_str(emoji.trim()) {
+            Ok(reaction) => Ok((code.to_lowercase(), reaction)),
+            Err(_) => Err(format!(
+                "Could not parse the {code} emoji {emoji:?} in {path}"
+            )),
+        })
+        .collect()
+}
+
+/// Reads a sheet column letter such as `C`.
+fn parse_column(var: &str, column: &str) -> Result<char, String> {
+    let mut chars = column.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_alphabetic() => Ok(letter.to_ascii_uppercase()),
+        _ => Err(format!(
+            "Expected {var} to be a single letter, got {column:?}"
+        )),
+    }
+}
+
+/// The column letter matching a stage number, e.g. `C` for stage 3.
+fn default_column(stage: u32) -> Option<char> {
+    (1..=26)
+        .contains(&stage)
+        .then(|| char::from(b'A' + (stage - 1) as u8))
+}
+
+/// Reads an emoji such as `✅` or `<:yes:1234>` from `var`.
+fn parse_emoji_env(vars: &EnvVars, var: &str, default: &str) -> Result<ReactionType, String> {
+    let emoji = env_var(vars, var).unwrap_or_else(|_| default.to_string());
+    ReactionType::from_str(emoji.trim())
+        .map_err(|_| format!("Could not parse {var} value {emoji:?} as an emoji"))
+}
+
+/// Sauce stage a command group operates on.
+///
+/// Each group reads `<GROUP>_STAGE` and `<GROUP>_MOVE_TARGET` from the
+/// environment, e.g. `LC_STAGE=3` and `LC_MOVE_TARGET=4`, falling back to the
+/// config file's `stages`. When no move target is configured, moves go to the
+/// stage right after `stage`.
+#[derive(Clone, Copy, PartialEq)]
+struct StageConfig {
+    stage: u32,
+    move_target: u32,
+}
+
+impl StageConfig {
+    fn from_env(
+        vars: &EnvVars,
+        group: &str,
+        default_stage: u32,
+        file: Option<&StageFile>,
+    ) -> Result<Self, String> {
+        let default = StageFile::default();
+        let file = file.unwrap_or(&default);
+        let group = group.to_uppercase();
+        let stage = parse_env(
+            vars,
+            &format!("{group}_STAGE"),
+            file.stage.unwrap_or(default_stage),
+        )?;
+        let move_target = parse_env(
+            vars,
+            &format!("{group}_MOVE_TARGET"),
+            file.move_target.unwrap_or(stage + 1),
+        )?;
+
+        Ok(Self { stage, move_target })
+    }
+}
+
+/// Settings read from the config file and the environment at startup and on
+/// `*reload`.
+#[derive(Clone, PartialEq)]
+struct Config {
+    bots: HashMap<&'static str, u64>,
+    stages: HashMap<&'static str, StageConfig>,
+    /// Column letter per group when `COLUMN_LETTERS` is on, empty otherwise.
+    columns: HashMap<&'static str, char>,
+    author_lookup_delay: Duration,
+    author_lookup_retries: u32,
+    confirm_deletes: bool,
+    sauce_keyword: String,
+    status_id: u32,
+    languages: HashMap<String, ReactionType>,
+    /// Custom emoji per language code, either `<:name:id>` or a guild emoji name.
+    language_emojis: HashMap<String, String>,
+    send_timeout: Duration,
+    /// Minimum gap between two messages the bot sends in one channel.
+    send_interval: Duration,
+    /// Extra command words from `COMMAND_ALIASES`, e.g. `rm` -> `qc delete`.
+    aliases: HashMap<String, String>,
+    /// Roles from `COMMAND_ROLES`, one of which is needed to run a command.
+    command_roles: HashMap<String, Vec<String>>,
+    /// Channels commands that talk to sauce may be used in; empty allows every
+    /// channel.
+    allowed_channels: HashSet<ChannelId>,
+    /// Where failures go when the bot can't post in the channel they happened in.
+    error_channel: Option<ChannelId>,
+    list_page_size: usize,
+    /// Largest id the stage commands accept, to catch typos like `9999999`.
+    max_id: u32,
+    /// What `*<group> all` sends, with `{stage}` standing in for the stage.
+    list_all: String,
+    /// Moves allowed by `MOVE_TRANSITIONS`; empty allows moving to any known stage.
+    transitions: HashSet<(u32, u32)>,
+    confirm_emoji: ReactionType,
+    cancel_emoji: ReactionType,
+    /// Reacting with this on a tracked embed moves its item, if set.
+    advance_emoji: Option<ReactionType>,
+}
+
+fn push_change<T: PartialEq + Display>(changes: &mut Vec<String>, name: &str, old: T, new: T) {
+    if old != new {
+        changes.push(format!("{name}: {old} -> {new}"));
+    }
+}
+
+impl Config {
+    fn from_env(vars: &EnvVars) -> Result<Self, String> {
+        Self::from_file(ConfigFile::load(vars)?, vars)
+    }
+
+    /// Resolves `file` against `vars`, which win where both are set.
+    fn from_file(file: ConfigFile, vars: &EnvVars) -> Result<Self, String> {
+        let bots = HashMap::from([
+            (
+                "sriracha",
+                parse_env(vars, "SRIRACHA_BOT_ID", file.sriracha_bot_id)?,
+            ),
+            (
+                "ohsheet",
+                parse_env(vars, "OHSHEET_BOT_ID", file.ohsheet_bot_id)?,
+            ),
+            ("lc", parse_env(vars, "LC_BOT_ID", file.lc_bot_id)?),
+            (
+                "fort checker",
+                parse_env(vars, "FORT_CHECKER_BOT_ID", file.fort_checker_bot_id)?,
+            ),
+        ]);
+
+        if let Some(group) = file
+            .stages
+            .keys()
+            .find(|group| !STAGES.contains(&group.as_str()))
+        {
+            return Err(format!(
+                "Unknown group '{group}' in the config file's stages"
+            ));
+        }
+        let mut stages = HashMap::new();
+        for (group, default_stage) in STAGES.into_iter().zip([3, 2, 1, 5]) {
+            stages.insert(
+                group,
+                StageConfig::from_env(vars, group, default_stage, file.stages.get(group))?,
+            );
+        }
+        if let Some(group) = file
+            .columns
+            .keys()
+            .find(|group| !STAGES.contains(&group.as_str()))
+        {
+            return Err(format!(
+                "Unknown group '{group}' in the config file's columns"
+            ));
+        }
+        let mut columns = HashMap::new();
+        if parse_env(vars, "COLUMN_LETTERS", file.column_letters)? {
+            for group in STAGES {
+                let var = format!("{}_COLUMN", group.to_uppercase());
+                let column = match env_var(vars, &var)
+                    .ok()
+                    .or_else(|| file.columns.get(group).cloned())
+                {
+                    Some(column) => parse_column(&var, &column)?,
+                    None => default_column(stages[group].stage).ok_or_else(|| {
+                        format!(
+                            "Expected {var} to be set, stage {} has no column letter",
+                            stages[group].stage
+                        )
+                    })?,
+                };
+                columns.insert(group, column);
+            }
+        }
+
+        let author_lookup_delay = Duration::from_millis(parse_env(
+            vars,
+            "AUTHOR_LOOKUP_DELAY_MS",
+            file.author_lookup_delay_ms,
+        )?);
+        let author_lookup_retries =
+            parse_env(vars, "AUTHOR_LOOKUP_RETRIES", file.author_lookup_retries)?;
+        let confirm_deletes = parse_env(vars, "CONFIRM_DELETES", file.confirm_deletes)?;
+        let sauce_keyword: String = parse_env(vars, "SAUCE_KEYWORD", file.sauce_keyword)?;
+        if sauce_keyword.is_empty() {
+            return Err("Expected SAUCE_KEYWORD to not be empty".to_string());
+        }
+        let status_id = parse_env(vars, "STATUS_ID", file.status_id)?;
+        let send_timeout =
+            Duration::from_millis(parse_env(vars, "SEND_TIMEOUT_MS", file.send_timeout_ms)?);
+        let send_interval =
+            Duration::from_millis(parse_env(vars, "SEND_INTERVAL_MS", file.send_interval_ms)?);
+        let aliases = match env_var(vars, "COMMAND_ALIASES") {
+            Ok(input) => parse_aliases(&input)?,
+            Err(_) => file
+                .command_aliases
+                .into_iter()
+                .map(|(alias, command)| (alias.trim().to_lowercase(), command.trim().to_string()))
+                .collect(),
+        };
+        let command_roles = match env_var(vars, "COMMAND_ROLES") {
+            Ok(input) => parse_command_roles(&input)?,
+            Err(_) => file
+                .command_roles
+                .into_iter()
+                .map(|(command, roles)| (command.trim().to_lowercase(), roles))
+                .collect(),
+        };
+        let allowed_channels =
+            parse_channel_ids(vars, "ALLOWED_CHANNEL_IDS", &file.allowed_channel_ids)?;
+        let error_channel = match env_var(vars, "ERROR_CHANNEL_ID") {
+            Ok(id) if !id.trim().is_empty() => Some(
+                id.trim()
+                    .parse()
+                    .map_err(|_| format!("Could not parse ERROR_CHANNEL_ID, got {id:?}"))?,
+            ),
+            _ => file.error_channel_id,
+        }
+        .map(ChannelId);
+        let max_id = parse_env(vars, "MAX_ID", file.max_id)?;
+        if max_id == 0 {
+            return Err("Expected MAX_ID to be at least 1".to_string());
+        }
+        let list_all: String = parse_env(vars, "LIST_ALL_COMMAND", file.list_all_command)?;
+        if !list_all.contains("{stage}") {
+            return Err("Expected LIST_ALL_COMMAND to contain {stage}".to_string());
+        }
+        let transitions = match env_var(vars, "MOVE_TRANSITIONS") {
+            Ok(input) => parse_transitions(&input)?,
+            Err(_) => file.move_transitions.into_iter().collect(),
+        };
+        if !transitions.is_empty() {
+            for group in STAGES {
+                let StageConfig { stage, move_target } = stages[group];
+                if !transitions.contains(&(stage, move_target)) {
+                    return Err(format!(
+                        "MOVE_TRANSITIONS does not allow the default {group} move {stage}>{move_target}"
+                    ));
+                }
+            }
+        }
+        let list_page_size = parse_env(vars, "LIST_PAGE_SIZE", file.list_page_size)?;
+        if list_page_size == 0 {
+            return Err("Expected LIST_PAGE_SIZE to be at least 1".to_string());
+        }
+
+        let confirm_emoji = parse_emoji_env(vars, "CONFIRM_EMOJI", &file.confirm_emoji)?;
+        let cancel_emoji = parse_emoji_env(vars, "CANCEL_EMOJI", &file.cancel_emoji)?;
+        if same_emoji(&confirm_emoji, &cancel_emoji) {
+            return Err("Expected CONFIRM_EMOJI and CANCEL_EMOJI to differ".to_string());
+        }
+        let advance_emoji =
+            env_var(vars, "ADVANCE_EMOJI").unwrap_or_else(|_| file.advance_emoji.clone());
+        let advance_emoji = match advance_emoji.trim() {
+            "" => None,
+            _ => Some(parse_emoji_env(vars, "ADVANCE_EMOJI", &file.advance_emoji)?),
+        };
+
+        let languages = load_languages(vars, file.languages.as_ref())?;
+        let mut language_emojis = HashMap::new();
+        for code in languages.keys() {
+            let emoji = env_var(vars, &format!("{}_EMOJI", code.to_uppercase()))
+                .ok()
+                .or_else(|| file.language_emojis.get(code).cloned())
+                .unwrap_or_default();
+            if !emoji.trim().is_empty() {
+                language_emojis.insert(code.clone(), emoji.trim().to_string());
+            }
+        }
+
+        Ok(Self {
+            bots,
+            stages,
+            columns,
+            author_lookup_delay,
+            author_lookup_retries,
+            confirm_deletes,
+            sauce_keyword,
+            status_id,
+            languages,
+            language_emojis,
+            send_timeout,
+            send_interval,
+            aliases,
+            command_roles,
+            allowed_channels,
+            error_channel,
+            list_page_size,
+            max_id,
+            list_all,
+            transitions,
+            confirm_emoji,
+            cancel_emoji,
+            advance_emoji,
+        })
+    }
+
+    fn changes(&self, new: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        let mut bot_names: Vec<_> = new.bots.keys().collect();
+        bot_names.sort();
+        for name in bot_names {
+            push_change(
+                &mut changes,
+                &format!("{name} bot id"),
+                self.bots[name],
+                new.bots[name],
+            );
+        }
+
+        for group in STAGES {
+            let (old_stage, new_stage) = (self.stages[group], new.stages[group]);
+            push_change(
+                &mut changes,
+                &format!("{group} stage"),
+                old_stage.stage,
+                new_stage.stage,
+            );
+            push_change(
+                &mut changes,
+                &format!("{group} move target"),
+                old_stage.move_target,
+                new_stage.move_target,
+            );
+        }
+
+        push_change(
+            &mut changes,
+            "author lookup delay (ms)",
+            self.author_lookup_delay.as_millis(),
+            new.author_lookup_delay.as_millis(),
+        );
+        push_change(
+            &mut changes,
+            "author lookup retries",
+            self.author_lookup_retries,
+            new.author_lookup_retries,
+        );
+        push_change(
+            &mut changes,
+            "confirm deletes",
+            self.confirm_deletes,
+            new.confirm_deletes,
+        );
+        push_change(
+            &mut changes,
+            "sauce keyword",
+            &self.sauce_keyword,
+            &new.sauce_keyword,
+        );
+        push_change(&mut changes, "status id", self.status_id, new.status_id);
+        push_change(
+            &mut changes,
+            "send timeout (ms)",
+            self.send_timeout.as_millis(),
+            new.send_timeout.as_millis(),
+        );
+        push_change(
+            &mut changes,
+            "send interval (ms)",
+            self.send_interval.as_millis(),
+            new.send_interval.as_millis(),
+        );
+        push_change(
+            &mut changes,
+            "columns",
+            self.column_list(),
+            new.column_list(),
+        );
+        push_change(&mut changes, "aliases", self.alias_list(), new.alias_list());
+        push_change(
+            &mut changes,
+            "command roles",
+            self.command_role_list(),
+            new.command_role_list(),
+        );
+        push_change(
+            &mut changes,
+            "allowed channels",
+            self.allowed_channel_list(),
+            new.allowed_channel_list(),
+        );
+        push_change(
+            &mut changes,
+            "error channel",
+            self.error_channel_name(),
+            new.error_channel_name(),
+        );
+        push_change(
+            &mut changes,
+            "list page size",
+            self.list_page_size,
+            new.list_page_size,
+        );
+        push_change(&mut changes, "max id", self.max_id, new.max_id);
+        push_change(
+            &mut changes,
+            "list all command",
+            &self.list_all,
+            &new.list_all,
+        );
+        push_change(
+            &mut changes,
+            "move transitions",
+            self.transition_list(),
+            new.transition_list(),
+        );
+        push_change(
+            &mut changes,
+            "confirm emoji",
+            &self.confirm_emoji,
+            &new.confirm_emoji,
+        );
+        push_change(
+            &mut changes,
+            "cancel emoji",
+            &self.cancel_emoji,
+            &new.cancel_emoji,
+        );
+        push_change(
+            &mut changes,
+            "advance emoji",
+            self.advance_emoji_name(),
+            new.advance_emoji_name(),
+        );
+
+        push_change(
+            &mut changes,
+            "languages",
+            self.language_list(),
+            new.language_list(),
+        );
+        for code in new.language_codes() {
+            push_change(
+                &mut changes,
+                &format!("{code} emoji"),
+                self.language_emojis
+                    .get(code)
+                    .map_or("flag", String::as_str),
+                new.language_emojis.get(code).map_or("flag", String::as_str),
+            );
+        }
+
+        changes
+    }
+
+    fn alias_list(&self) -> String {
+        let mut aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .map(|(alias, command)| format!("{alias}={command}"))
+            .collect();
+        aliases.sort();
+        aliases.join(", ")
+    }
+
+    fn command_role_list(&self) -> String {
+        let mut command_roles: Vec<_> = self
+            .command_roles
+            .iter()
+            .map(|(command, roles)| format!("{command}={}", roles.join("|")))
+            .collect();
+        command_roles.sort();
+        command_roles.join(", ")
+    }
+
+    /// The roles needed for `command_name`, checking the command itself, then
+    /// its action, then the sauce action it sends, then its group. So `move`
+    /// also covers `lc_move_range`, `lc_swap` and `lc_undo`, which all send
+    /// `sauce move`.
+    fn required_roles(&self, command_name: &str) -> Option<&[String]> {
+        let (group, action) = command_name.split_once('_').unwrap_or((command_name, ""));
+        let sauce_action = match action {
+            "move_range" | "swap" | "undo" => "move",
+            _ => "",
+        };
+        [command_name, action, sauce_action, group]
+            .into_iter()
+            .filter(|key| !key.is_empty())
+            .find_map(|key| self.command_roles.get(key))
+            .map(Vec::as_slice)
+    }
+
+    fn allowed_channel_list(&self) -> String {
+        if self.allowed_channels.is_empty() {
+            return "all".to_string();
+        }
+
+        let mut channels: Vec<_> = self.allowed_channels.iter().collect();
+        channels.sort();
+        channels
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn error_channel_name(&self) -> String {
+        self.error_channel
+            .map_or("none".to_string(), |channel_id| channel_id.to_string())
+    }
+
+    fn advance_emoji_name(&self) -> String {
+        self.advance_emoji
+            .as_ref()
+            .map_or("none".to_string(), ReactionType::to_string)
+    }
+
+    fn allows_channel(&self, channel_id: ChannelId) -> bool {
+        self.allowed_channels.is_empty() || self.allowed_channels.contains(&channel_id)
+    }
+
+    /// Whether `command_name` may run in `channel_id`. Only commands that talk
+    /// to sauce are limited to the allowed channels.
+    fn allows_command_in(&self, command_name: &str, channel_id: ChannelId) -> bool {
+        !sends_to_sauce(command_name) || self.allows_channel(channel_id)
+    }
+
+    fn summary(&self) -> String {
+        let mut bots: Vec<_> = self.bots.iter().collect();
+        bots.sort();
+        let bots = bots
+            .into_iter()
+            .map(|(name, id)| format!("{name}={id}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let stages = STAGES
+            .map(|group| {
+                let StageConfig { stage, move_target } = self.stages[group];
+                format!("{group}={stage}->{move_target}")
+            })
+            .join(", ");
+
+        format!(
+            "sauce keyword '{}', author lookup delay {}ms, author lookup retries {}, \
+             confirm deletes {}, status id {}, send timeout {}ms, send interval {}ms, max id {}, \
+             list all command '{}', \
+             bots [{bots}], stages [{stages}], columns [{}], move transitions [{}], \
+             aliases [{}], command roles [{}], allowed channels [{}], error channel {}, \
+             languages [{}], \
+             delete emojis {}/{}, advance emoji {}",
+            self.sauce_keyword,
+            self.author_lookup_delay.as_millis(),
+            self.author_lookup_retries,
+            self.confirm_deletes,
+            self.status_id,
+            self.send_timeout.as_millis(),
+            self.send_interval.as_millis(),
+            self.max_id,
+            self.list_all,
+            self.column_list(),
+            self.transition_list(),
+            self.alias_list(),
+            self.command_role_list(),
+            self.allowed_channel_list(),
+            self.error_channel_name(),
+            self.language_list(),
+            self.confirm_emoji,
+            self.cancel_emoji,
+            self.advance_emoji_name(),
+        )
+    }
+
+    fn language_codes(&self) -> Vec<&str> {
+        let mut codes: Vec<_> = self.languages.keys().map(String::as_str).collect();
+        codes.sort();
+        codes
+    }
+
+    fn language_list(&self) -> String {
+        self.language_codes()
+            .into_iter()
+            .map(|code| format!("{code}={}", self.languages[code]))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// How `stage` is written in sauce commands: its group's column letter
+    /// with `COLUMN_LETTERS` on, otherwise the stage number.
+    fn column(&self, stage: u32) -> String {
+        if self.columns.is_empty() {
+            return stage.to_string();
+        }
+
+        STAGES
+            .into_iter()
+            .find(|group| self.stages[group].stage == stage)
+            .and_then(|group| self.columns.get(group).copied())
+            .or_else(|| default_column(stage))
+            .map_or_else(|| stage.to_string(), String::from)
+    }
+
+    fn column_list(&self) -> String {
+        if self.columns.is_empty() {
+            return "numbers".to_string();
+        }
+
+        STAGES
+            .map(|group| format!("{group}={}", self.columns[group]))
+            .join(", ")
+    }
+
+    fn transition_list(&self) -> String {
+        if self.transitions.is_empty() {
+            return "any".to_string();
+        }
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort();
+        transitions
+            .into_iter()
+            .map(|(from, to)| format!("{from}>{to}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn allows_move(&self, from: u32, to: u32) -> bool {
+        self.transitions.is_empty() || self.transitions.contains(&(from, to))
+    }
+
+    fn is_known_stage(&self, stage: u32) -> bool {
+        self.stages
+            .values()
+            .any(|config| config.stage == stage || config.move_target == stage)
+    }
+
+    fn sriracha_bot_ids(&self) -> [u64; 2] {
+        [self.bots["sriracha"], self.bots["ohsheet"]]
+    }
+
+    fn is_sriracha_bot(&self, user: &User) -> bool {
+        let id = *user.id.as_u64();
+        id == self.bots["sriracha"] || id == self.bots["ohsheet"]
+    }
+
+    fn is_lc_bot(&self, user: &User) -> bool {
+        let id = *user.id.as_u64();
+        id == self.bots["ohsheet"] || id == self.bots["lc"] || id == self.bots["fort checker"]
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    fn user(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId(id);
+        user
+    }
+
+    #[test]
+    fn recognizes_each_configured_bot() {
+        let config = Config::from_file(ConfigFile::default(), &EnvVars::new()).unwrap();
+        for (name, sriracha, lc) in [
+            ("sriracha", true, false),
+            ("ohsheet", true, true),
+            ("lc", false, true),
+            ("fort checker", false, true),
+        ] {
+            let bot = user(config.bots[name]);
+            assert_eq!(config.is_sriracha_bot(&bot), sriracha, "{name}");
+            assert_eq!(config.is_lc_bot(&bot), lc, "{name}");
+        }
+
+        let person = user(1234);
+        assert!(!config.is_sriracha_bot(&person));
+        assert!(!config.is_lc_bot(&person));
+    }
+
+    #[test]
+    fn move_roles_cover_every_command_that_moves() {
+        let config = Config::from_file(
+            ConfigFile {
+                command_roles: HashMap::from([
+                    ("move".to_string(), vec!["Staff".to_string()]),
+                    ("delete".to_string(), vec!["Admin".to_string()]),
+                    ("qc_swap".to_string(), vec!["QC".to_string()]),
+                ]),
+                ..ConfigFile::default()
+            },
+            &EnvVars::new(),
+        )
+        .unwrap();
+        let roles = |command| config.required_roles(command).map(|roles| roles.join("|"));
+
+        for command in [
+            "lc_move",
+            "lc_move_range",
+            "lc_swap",
+            "lc_undo",
+            "ts_move_range",
+        ] {
+            assert_eq!(roles(command).as_deref(), Some("Staff"), "{command}");
+        }
+        assert_eq!(roles("st_delete").as_deref(), Some("Admin"));
+        assert_eq!(roles("qc_swap").as_deref(), Some("QC"));
+        assert_eq!(roles("lc_list"), None);
+        assert_eq!(roles("lc_retry"), None);
+        assert_eq!(roles("ping"), None);
+    }
+
+    #[test]
+    fn allowed_channels_cover_every_command_that_talks_to_sauce() {
+        let config = Config::from_file(
+            ConfigFile {
+                allowed_channel_ids: vec![1],
+                ..ConfigFile::default()
+            },
+            &EnvVars::new(),
+        )
+        .unwrap();
+
+        for command in ["status", "lookup", "author_retry", "lc_list", "qc_move"] {
+            assert!(config.allows_command_in(command, ChannelId(1)), "{command}");
+            assert!(
+                !config.allows_command_in(command, ChannelId(2)),
+                "{command}"
+            );
+        }
+        assert!(config.allows_command_in("ping", ChannelId(2)));
+        assert!(config.allows_command_in("author", ChannelId(2)));
+    }
+}
+
+struct ConfigContainer;
+
+impl TypeMapKey for ConfigContainer {
+    type Value = Arc<RwLock<Arc<Config>>>;
+}
+
+async fn config_handle(ctx: &Context) -> Arc<RwLock<Arc<Config>>> {
+    let data = ctx.data.read().await;
+    data.get::<ConfigContainer>()
+        .expect("Expected ConfigContainer in TypeMap")
+        .clone()
+}
+
+async fn current_config(ctx: &Context) -> Arc<Config> {
+    config_handle(ctx).await.read().await.clone()
+}
+
+/// The running client's shard manager. Every connect attempt builds a new
+/// client, so this is swapped out rather than fixed at startup.
+type ShardManagerSlot = Arc<RwLock<Option<Arc<Mutex<ShardManager>>>>>;
+
+pub struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+    type Value = ShardManagerSlot;
+}
+
+pub struct CommandPrefix;
+
+impl TypeMapKey for CommandPrefix {
+    type Value = String;
+}
+
+/// What `*reload` needs to rebuild the environment: the `.env` file found at
+/// startup and the variables that were set before it was applied.
+pub struct StartupEnv {
+    env_file: std::path::PathBuf,
+    process_env: EnvVars,
+}
+
+impl TypeMapKey for StartupEnv {
+    type Value = Arc<StartupEnv>;
+}
+
+/// When the bot started, for `*about`.
+pub struct StartedAt;
+
+impl TypeMapKey for StartedAt {
+    type Value = Instant;
+}
+
+/// Whether the presence intent was requested, so `*upstream` can tell
+/// "offline" apart from "unknown".
+pub struct PresencesEnabled;
+
+impl TypeMapKey for PresencesEnabled {
+    type Value = bool;
+}
+
+pub struct BotOwners;
+
+impl TypeMapKey for BotOwners {
+    type Value = HashSet<UserId>;
+}
+
+async fn is_owner(ctx: &Context, user_id: UserId) -> bool {
+    let data = ctx.data.read().await;
+    data.get::<BotOwners>()
+        .is_some_and(|owners| owners.contains(&user_id))
+}
+
+/// Commands that have started but not finished, waited on at shutdown.
+pub struct InFlightCommands;
+
+impl TypeMapKey for InFlightCommands {
+    type Value = Arc<AtomicUsize>;
+}
+
+async fn in_flight(ctx: &Context) -> Arc<AtomicUsize> {
+    let data = ctx.data.read().await;
+    data.get::<InFlightCommands>()
+        .expect("Expected InFlightCommands in TypeMap")
+        .clone()
+}
+
+/// Counts a command as in flight until dropped, so one that returns early or
+/// panics doesn't leave shutdown waiting on it.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    async fn start(ctx: &Context) -> Self {
+        let counter = in_flight(ctx).await;
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The sauce bucket's delay for slash commands, which the framework's buckets
+/// never see.
+pub struct SlashCooldown {
+    delay: Duration,
+    last_used: Mutex<HashMap<UserId, Instant>>,
+}
+
+impl TypeMapKey for SlashCooldown {
+    type Value = Arc<SlashCooldown>;
+}
+
+impl SlashCooldown {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            last_used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long `user_id` still has to wait, or `None` if they can go ahead,
+    /// which starts their next wait.
+    async fn wait_for(&self, user_id: UserId) -> Option<Duration> {
+        let now = Instant::now();
+        let mut last_used = self.last_used.lock().await;
+        let wait = last_used
+            .get(&user_id)
+            .map(|used_at| self.delay.saturating_sub(now - *used_at))
+            .filter(|wait| !wait.is_zero());
+        if wait.is_none() {
+            last_used.insert(user_id, now);
+        }
+        wait
+    }
+}
+
+pub struct DryRunChannels;
+
+impl TypeMapKey for DryRunChannels {
+    type Value = Arc<RwLock<HashSet<ChannelId>>>;
+}
+
+/// The stage picked with `*use` in each channel, which bare stage commands
+/// like `*move 5` act on.
+pub struct ActiveStages;
+
+impl TypeMapKey for ActiveStages {
+    type Value = Arc<RwLock<HashMap<ChannelId, &'static str>>>;
+}
+
+async fn active_stage(ctx: &Context, channel_id: ChannelId) -> Option<&'static str> {
+    let data = ctx.data.read().await;
+    match data.get::<ActiveStages>() {
+        Some(active_stages) => active_stages.read().await.get(&channel_id).copied(),
+        None => None,
+    }
+}
+
+/// Channels where commands confirm what they sent, toggled by `*ack`.
+pub struct AckChannels;
+
+impl TypeMapKey for AckChannels {
+    type Value = Arc<RwLock<HashSet<ChannelId>>>;
+}
+
+async fn acks_enabled(ctx: &Context, channel_id: ChannelId) -> bool {
+    let data = ctx.data.read().await;
+    match data.get::<AckChannels>() {
+        Some(ack_channels) => ack_channels.read().await.contains(&channel_id),
+        None => false,
+    }
+}
+
+async fn is_dry_run(ctx: &Context, channel_id: ChannelId) -> bool {
+    let data = ctx.data.read().await;
+    match data.get::<DryRunChannels>() {
+        Some(dry_run_channels) => dry_run_channels.read().await.contains(&channel_id),
+        None => false,
+    }
+}
+
+/// Guild that tracked state belongs to. Direct messages have no guild and
+/// share a bucket of their own.
+type GuildKey = Option<GuildId>;
+
+/// State tracked from upstream bot messages, shared by the handler and the
+/// commands that act on it.
+#[derive(Default)]
+pub struct TrackedState {
+    last_stage_commands: RwLock<HashMap<(GuildKey, &'static str), String>>,
+    pending_stage_commands: RwLock<HashMap<(GuildKey, &'static str), MessageId>>,
+    /// Recent sriracha embeds per channel, newest first.
+    last_sriracha_embed_messages: RwLock<HashMap<ChannelId, VecDeque<Message>>>,
+    last_author: RwLock<HashMap<GuildKey, String>>,
+    last_author_errors: RwLock<HashMap<ChannelId, Instant>>,
+    last_lookup_message_id: RwLock<Option<MessageId>>,
+    /// "Looking up" messages whose author could not be parsed, for `*reparse`.
+    failed_lookups: RwLock<HashMap<ChannelId, String>>,
+    last_moves: RwLock<HashMap<(GuildKey, &'static str), LastMove>>,
+    history: RwLock<VecDeque<HistoryEntry>>,
+    reaction_locks: Mutex<HashMap<MessageId, Arc<Mutex<()>>>>,
+    /// The last stage command sent in each channel, to match sriracha's reply to it.
+    issued_commands: RwLock<HashMap<ChannelId, IssuedCommand>>,
+    sauce_summaries: RwLock<HashMap<(ChannelId, &'static str), SauceSummary>>,
+    /// When each language was last applied to an embed, for `LANGUAGE_COOLDOWN`.
+    language_reactions: RwLock<HashMap<(MessageId, String), Instant>>,
+    /// Authors waiting to be looked up per channel, gathered over
+    /// `LOOKUP_COALESCE_WINDOW` so a batch of "Looking up" messages sends
+    /// one lookup per author.
+    pending_lookups: Mutex<HashMap<ChannelId, Vec<String>>>,
+    /// The group and command of the last sauce command sent in each channel,
+    /// for `*again`.
+    last_sent: RwLock<HashMap<(GuildKey, ChannelId), (String, String)>>,
+    /// Delete confirmations waiting on their invoker, cancelled by `*cancel`.
+    pending_deletes: Mutex<HashMap<(ChannelId, UserId), oneshot::Sender<()>>>,
+    /// The earliest time the next message may go out in each channel.
+    send_slots: Mutex<HashMap<ChannelId, Arc<Mutex<Instant>>>>,
+    /// Embeds already moved by `ADVANCE_EMOJI`, so a second reaction doesn't move them again.
+    advanced_embeds: Mutex<HashSet<MessageId>>,
+    /// Held while `save_state` writes, so concurrent saves can't interleave.
+    save_lock: Mutex<()>,
+}
+
+impl TrackedState {
+    async fn push_embed(&self, message: Message) {
+        let mut last_sriracha_embed_messages = self.last_sriracha_embed_messages.write().await;
+        let embeds = last_sriracha_embed_messages
+            .entry(message.channel_id)
+            .or_default();
+        embeds.retain(|embed| embed.id != message.id);
+        embeds.push_front(message);
+        embeds.truncate(EMBED_HISTORY);
+    }
+
+    /// Swaps in the edited copy of a tracked embed, keeping its place, or
+    /// drops it when the edit removed its embeds.
+    async fn refresh_embed(&self, message: Message) {
+        let mut last_sriracha_embed_messages = self.last_sriracha_embed_messages.write().await;
+        let embeds = match last_sriracha_embed_messages.get_mut(&message.channel_id) {
+            Some(embeds) => embeds,
+            None => return,
+        };
+        if message.embeds.is_empty() {
+            embeds.retain(|embed| embed.id != message.id);
+        } else if let Some(embed) = embeds.iter_mut().find(|embed| embed.id == message.id) {
+            *embed = message;
+        }
+    }
+
+    /// Empties everything remembered about stages, embeds and authors,
+    /// returning a description of what was cleared. History, send pacing and
+    /// in-progress confirmations are left alone.
+    async fn flush(&self) -> Vec<String> {
+        fn cleared(cleared: &mut Vec<String>, count: usize, what: &str) {
+            if count > 0 {
+                cleared.push(format!("{count} {what}"));
+            }
+        }
+
+        let mut cleared_items = Vec::new();
+        let stage_commands = {
+            let mut last_stage_commands = self.last_stage_commands.write().await;
+            let count = last_stage_commands.len();
+            last_stage_commands.clear();
+            self.pending_stage_commands.write().await.clear();
+            count
+        };
+        cleared(&mut cleared_items, stage_commands, "last stage commands");
+        let embeds = {
+            let mut last_sriracha_embed_messages = self.last_sriracha_embed_messages.write().await;
+            let count = last_sriracha_embed_messages
+                .values()
+                .map(VecDeque::len)
+                .sum();
+            last_sriracha_embed_messages.clear();
+            self.language_reactions.write().await.clear();
+            self.advanced_embeds.lock().await.clear();
+            count
+        };
+        cleared(&mut cleared_items, embeds, "tracked embeds");
+        let authors = {
+            let mut last_author = self.last_author.write().await;
+            let count = last_author.len();
+            last_author.clear();
+            self.last_author_errors.write().await.clear();
+            *self.last_lookup_message_id.write().await = None;
+            count
+        };
+        cleared(&mut cleared_items, authors, "last authors");
+        let failed_lookups = {
+            let mut failed_lookups = self.failed_lookups.write().await;
+            let count = failed_lookups.len();
+            failed_lookups.clear();
+            self.pending_lookups.lock().await.clear();
+            count
+        };
+        cleared(&mut cleared_items, failed_lookups, "failed lookups");
+        let moves = {
+            let mut last_moves = self.last_moves.write().await;
+            let count = last_moves.len();
+            last_moves.clear();
+            count
+        };
+        cleared(&mut cleared_items, moves, "undoable moves");
+        let replies = {
+            let mut issued_commands = self.issued_commands.write().await;
+            let mut sauce_summaries = self.sauce_summaries.write().await;
+            let count = issued_commands.len() + sauce_summaries.len();
+            issued_commands.clear();
+            sauce_summaries.clear();
+            count
+        };
+        cleared(&mut cleared_items, replies, "sriracha replies");
+        let last_sent = {
+            let mut last_sent = self.last_sent.write().await;
+            let count = last_sent.len();
+            last_sent.clear();
+            count
+        };
+        cleared(&mut cleared_items, last_sent, "last sent commands");
+
+        cleared_items
+    }
+
+    /// Runs `f` while holding the reaction lock for `message_id`, so two flag
+    /// commands on the same embed can't interleave their delete and react.
+    async fn with_reaction_lock<T>(
+        &self,
+        message_id: MessageId,
+        f: impl std::future::Future<Output = T>,
+    ) -> T {
+        let lock = self
+            .reaction_locks
+            .lock()
+            .await
+            .entry(message_id)
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().await;
+            f.await
+        };
+
+        let mut locks = self.reaction_locks.lock().await;
+        // Only the map and this call hold the lock, so nobody else is waiting.
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(&message_id);
+        }
+
+        result
+    }
+}
+
+/// A sauce command sent on someone's behalf, shown by `*history`.
+struct HistoryEntry {
+    sent_at: Timestamp,
+    user_id: UserId,
+    command: String,
+}
+
+struct IssuedCommand {
+    group: &'static str,
+    command: String,
+    sent_at: Instant,
+}
+
+/// What sriracha answered to a stage command, shown by `*status`.
+struct SauceSummary {
+    command: String,
+    summary: String,
+    seen_at: Timestamp,
+}
+
+/// A move issued through the bot, kept so it can be undone.
+#[derive(Clone)]
+struct LastMove {
+    stage: u32,
+    /// Where the moved items ended up in `target`, read from sriracha's
+    /// replies, since moved items go to the end of the stage.
+    ids: Vec<u32>,
+    target: u32,
+}
+
+pub struct TrackedStateContainer;
+
+impl TypeMapKey for TrackedStateContainer {
+    type Value = Arc<TrackedState>;
+}
+
+async fn tracked_state(ctx: &Context) -> Arc<TrackedState> {
+    let data = ctx.data.read().await;
+    data.get::<TrackedStateContainer>()
+        .expect("Expected TrackedStateContainer in TypeMap")
+        .clone()
+}
+
+/// Usage counters reported by `*stats`.
+#[derive(Default)]
+pub struct Metrics {
+    lc_commands: AtomicU64,
+    st_commands: AtomicU64,
+    qc_commands: AtomicU64,
+    ts_commands: AtomicU64,
+    lookups_succeeded: AtomicU64,
+    lookups_failed: AtomicU64,
+    reactions_applied: AtomicU64,
+}
+
+impl Metrics {
+    fn counters(&self) -> [(&'static str, &AtomicU64); 7] {
+        [
+            ("lc commands", &self.lc_commands),
+            ("st commands", &self.st_commands),
+            ("qc commands", &self.qc_commands),
+            ("ts commands", &self.ts_commands),
+            ("author lookups succeeded", &self.lookups_succeeded),
+            ("author lookups failed", &self.lookups_failed),
+            ("language reactions applied", &self.reactions_applied),
+        ]
+    }
+
+    fn stage_commands(&self, group: &str) -> Option<&AtomicU64> {
+        match group {
+            "lc" => Some(&self.lc_commands),
+            "st" => Some(&self.st_commands),
+            "qc" => Some(&self.qc_commands),
+            "ts" => Some(&self.ts_commands),
+            _ => None,
+        }
+    }
+}
+
+pub struct MetricsContainer;
+
+impl TypeMapKey for MetricsContainer {
+    type Value = Arc<Metrics>;
+}
+
+async fn metrics(ctx: &Context) -> Arc<Metrics> {
+    let data = ctx.data.read().await;
+    data.get::<MetricsContainer>()
+        .expect("Expected MetricsContainer in TypeMap")
+        .clone()
+}
+
+struct Handler {
+    started_at: Instant,
+    /// How often to log a keepalive line, `None` when disabled.
+    keepalive_interval: Option<Duration>,
+    /// Shared by the clients of every connect attempt, so a reconnect doesn't
+    /// start a second keepalive task.
+    keepalive_started: Arc<AtomicBool>,
+    /// Set once `ready` has fired, for `/healthz`.
+    ready: Arc<AtomicBool>,
+}
+
+const STAGES: [&str; 4] = ["lc", "st", "qc", "ts"];
+
+/// General commands that send something to sauce bot, and so fall under
+/// `ALLOWED_CHANNEL_IDS` like the stage commands do.
+const SAUCE_COMMANDS: [&str; 7] = [
+    "status",
+    "again",
+    "sauceauthor",
+    "reparse",
+    "lookup",
+    "raw",
+    "author_retry",
+];
+
+fn sends_to_sauce(command_name: &str) -> bool {
+    let group = command_name.split('_').next().unwrap_or_default();
+    STAGES.contains(&group) || SAUCE_COMMANDS.contains(&command_name)
+}
+
+const MAX_IDS: usize = 20;
+
+/// How many sent sauce commands are kept for `*history`.
+const HISTORY_CAPACITY: usize = 200;
+
+/// How many entries `*history` shows.
+const HISTORY_SHOWN: usize = 10;
+
+/// How long an echoed stage command has to stay the latest one before it is
+/// stored, so quick edits and re-posts only record the final command.
+const ECHO_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Extra wait before retrying a message Discord still rejected with a 429.
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(2);
+
+/// How long to wait for sriracha to answer a sauce command.
+const SAUCE_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to keep gathering "Looking up" messages before looking anyone up.
+const LOOKUP_COALESCE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Minimum time between "Could not find author" replies in one channel.
+const AUTHOR_ERROR_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Repeating the same language command on an embed within this is a no-op.
+const LANGUAGE_COOLDOWN: Duration = Duration::from_secs(5);
+
+const CONFIRM_EMOJI: &str = "✅";
+
+const CANCEL_EMOJI: &str = "❌";
+
+/// How many ids or id ranges a delete confirmation spells out.
+const DELETE_PREVIEW_RUNS: usize = 20;
+
+/// How long the invoker of a delete has to confirm it.
+const DELETE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PREVIOUS_PAGE_EMOJI: &str = "◀️";
+const NEXT_PAGE_EMOJI: &str = "▶️";
+
+/// How long a paginated list keeps listening for page reactions.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Wait before the first reconnect after `client.start` fails, doubled on
+/// every further attempt.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How often shutdown checks whether in-flight commands have finished.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pause between the list commands sent by `*status`.
+const STATUS_SEND_DELAY: Duration = Duration::from_secs(1);
+
+/// How many messages `*rescan` looks back through, Discord's maximum per fetch.
+const RESCAN_LIMIT: u64 = 100;
+
+/// How many recent sriracha embeds are kept per channel for `*en 2` and co.
+const EMBED_HISTORY: usize = 5;
+
+/// Longest `*embed dump` output, leaving room for the code fence.
+const EMBED_DUMP_LIMIT: usize = 1900;
+
+/// Languages used when there is no languages file.
+const LANGUAGES: [(&str, &str); 5] = [
+    ("en", "🇺🇸"),
+    ("jp", "🇯🇵"),
+    ("kr", "🇰🇷"),
+    ("cn", "🇨🇳"),
+    ("fr", "🇫🇷"),
+];
+
+const NO_EMBED_TRACKED: &str = "No sriracha embed has been seen in this channel yet";
+
+fn echoed_stage(content: &str) -> Option<&'static str> {
+    let command = content.strip_prefix('.')?;
+    STAGES.into_iter().find(|stage| command.starts_with(stage))
+}
+
+fn should_store_echo(
+    pending: Option<MessageId>,
+    echo: MessageId,
+    previous: Option<&String>,
+    content: &str,
+) -> bool {
+    pending == Some(echo) && previous.map(String::as_str) != Some(content)
+}
+
+#[cfg(test)]
+mod echo_tests {
+    use super::*;
+
+    #[test]
+    fn stores_the_pending_echo_when_it_changes() {
+        let previous = ".lc 3#4".to_string();
+        assert!(should_store_echo(
+            Some(MessageId(2)),
+            MessageId(2),
+            Some(&previous),
+            ".lc 3#5"
+        ));
+        assert!(should_store_echo(
+            Some(MessageId(2)),
+            MessageId(2),
+            None,
+            ".lc 3#5"
+        ));
+    }
+
+    #[test]
+    fn skips_a_superseded_echo() {
+        assert!(!should_store_echo(
+            Some(MessageId(3)),
+            MessageId(2),
+            None,
+            ".lc 3#5"
+        ));
+        assert!(!should_store_echo(None, MessageId(2), None, ".lc 3#5"));
+    }
+
+    #[test]
+    fn skips_an_unchanged_echo() {
+        let previous = ".lc 3#5".to_string();
+        assert!(!should_store_echo(
+            Some(MessageId(2)),
+            MessageId(2),
+            Some(&previous),
+            ".lc 3#5"
+        ));
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedState {
+    #[serde(default)]
+    stage_commands: Vec<PersistedStageCommand>,
+    #[serde(default)]
+    last_embeds: Vec<PersistedMessage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedStageCommand {
+    guild_id: Option<u64>,
+    stage: String,
+    command: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedMessage {
+    channel_id: u64,
+    message_id: u64,
+}
+
+fn state_file_path() -> String {
+    env::var("STATE_FILE").unwrap_or_else(|_| "state.json".to_string())
+}
+
+async fn save_state(tracked: &TrackedState) {
+    // Taken before reading, so whichever save writes last has the newest state.
+    let _guard = tracked.save_lock.lock().await;
+    let state = PersistedState {
+        stage_commands: tracked
+            .last_stage_commands
+            .read()
+            .await
+            .iter()
+            .map(|((guild_id, stage), command)| PersistedStageCommand {
+                guild_id: guild_id.map(|guild_id| *guild_id.as_u64()),
+                stage: stage.to_string(),
+                command: command.clone(),
+            })
+            .collect(),
+        last_embeds: tracked
+            .last_sriracha_embed_messages
+            .read()
+            .await
+            .values()
+            // Oldest first, so loading them in order leaves the newest in front.
+            .flat_map(|embeds| embeds.iter().rev())
+            .map(|message| PersistedMessage {
+                channel_id: *message.channel_id.as_u64(),
+                message_id: *message.id.as_u64(),
+            })
+            .collect(),
+    };
+
+    let contents = match serde_json::to_string_pretty(&state) {
+        Ok(contents) => contents,
+        Err(why) => {
+            error!("Could not serialize state: {:?}", why);
+            return;
+        }
+    };
+
+    // Written to a temporary file and renamed over the old one, so a crash
+    // mid-write can't leave truncated JSON behind.
+    let path = state_file_path();
+    let temp_path = format!("{path}.tmp");
+    if let Err(why) = tokio::fs::write(&temp_path, contents).await {
+        error!("Could not save state: {:?}", why);
+        return;
+    }
+    if let Err(why) = tokio::fs::rename(&temp_path, &path).await {
+        error!("Could not save state: {:?}", why);
+    }
+}
+
+async fn load_state(ctx: &Context, tracked: &TrackedState) {
+    let contents = match tokio::fs::read_to_string(state_file_path()).await {
+        Ok(contents) => contents,
+        Err(why) if why.kind() == ErrorKind::NotFound => return,
+        Err(why) => {
+            error!("Could not read state: {:?}", why);
+            return;
+        }
+    };
+
+    let state: PersistedState = match serde_json::from_str(&contents) {
+        Ok(state) => state,
+        Err(why) => {
+            error!("Could not parse state: {:?}", why);
+            return;
+        }
+    };
+
+    {
+        let mut last_stage_commands = tracked.last_stage_commands.write().await;
+        for stage_command in state.stage_commands {
+            let guild_id = stage_command.guild_id.map(GuildId);
+            if let Some(stage) = STAGES
+                .into_iter()
+                .find(|known| *known == stage_command.stage)
+            {
+                last_stage_commands.insert((guild_id, stage), stage_command.command);
+            }
+        }
+    }
+
+    for embed in state.last_embeds {
+        match ctx
+            .http
+            .get_message(embed.channel_id, embed.message_id)
+            .await
+        {
+            Ok(message) => {
+                tracked.push_embed(message).await;
+                info!("Restored last sriracha embed message: {}", embed.message_id);
+            }
+            Err(why) => warn!(
+                "Stored sriracha embed message {} is no longer available: {:?}",
+                embed.message_id, why
+            ),
+        }
+    }
+}
+
+/// Removes one pair of quotes or brackets wrapped around the whole input.
+fn strip_wrapping(input: &str) -> &str {
+    let input = input.trim();
+    for (open, close) in [('"', '"'), ('“', '”'), ('\'', '\''), ('[', ']'), ('(', ')')] {
+        if let Some(inner) = input
+            .strip_prefix(open)
+            .and_then(|rest| rest.strip_suffix(close))
+        {
+            return inner.trim();
+        }
+    }
+
+    input
+}
+
+/// Finds the `stage#id` at the start of a result line like `**3#12** Title`,
+/// or `**C#12** Title` with `COLUMN_LETTERS`. The stage comes back as written.
+fn item_get(input: &str) -> IResult<&str, (&str, u32)> {
+    let (input, stage) = alt((numbered_stage, lettered_stage))(input)?;
+    let (input, id) = map_res(digit1, str::parse)(input)?;
+
+    Ok((input, (stage, id)))
+}
+
+fn numbered_stage(input: &str) -> IResult<&str, &str> {
+    let (input, _) = take_till(|c: char| c.is_ascii_digit())(input)?;
+    terminated(digit1, char('#'))(input)
+}
+
+fn lettered_stage(input: &str) -> IResult<&str, &str> {
+    let (input, _) = take_till(|c: char| c.is_ascii_alphabetic())(input)?;
+    terminated(recognize(satisfy(|c| c.is_ascii_uppercase())), char('#'))(input)
+}
+
+#[cfg(test)]
+mod item_tests {
+    use super::*;
+
+    #[test]
+    fn finds_numbered_items() {
+        assert_eq!(item_get("**3#12** Title"), Ok(("** Title", ("3", 12))));
+        assert_eq!(item_get("`1#4` Other"), Ok(("` Other", ("1", 4))));
+    }
+
+    #[test]
+    fn finds_lettered_items() {
+        assert_eq!(item_get("**C#12** Title"), Ok(("** Title", ("C", 12))));
+        assert_eq!(item_get("A#1"), Ok(("", ("A", 1))));
+    }
+
+    #[test]
+    fn finds_where_a_move_landed() {
+        let lines = ["Moved".to_string(), "**4#17** Title".to_string()];
+        assert_eq!(find_landed_id(&lines, "4", 4), Some(17));
+        assert_eq!(find_landed_id(&lines, "D", 4), Some(17));
+        assert_eq!(find_landed_id(&lines, "5", 5), None);
+
+        let lines = ["**3#5** Title".to_string(), "**D#9** Title".to_string()];
+        assert_eq!(find_landed_id(&lines, "D", 4), Some(9));
+    }
+
+    #[test]
+    fn rejects_lines_without_an_item() {
+        assert!(item_get("Nothing found").is_err());
+        assert!(item_get("**CD#12** Title").is_err());
+        assert!(item_get("3 items").is_err());
+    }
+}
+
+/// Shortens a sriracha reply to its item count and first few titles.
+fn summarize_reply(reply: &Message) -> String {
+    let lines = reply_lines(reply);
+    let titles: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| item_get(line).ok())
+        .map(|(title, _)| title.trim_matches(|c: char| c.is_whitespace() || "*`-:".contains(c)))
+        .filter(|title| !title.is_empty())
+        .collect();
+
+    if titles.is_empty() {
+        return lines
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "empty reply".to_string());
+    }
+
+    let shown = titles
+        .iter()
+        .take(3)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let more = if titles.len() > 3 { ", ..." } else { "" };
+    format!("{} item(s): {shown}{more}", titles.len())
+}
+
+async fn capture_summary(tracked: &TrackedState, msg: &Message) {
+    let issued = match tracked
+        .issued_commands
+        .write()
+        .await
+        .remove(&msg.channel_id)
+    {
+        Some(issued) if issued.sent_at.elapsed() < SAUCE_REPLY_TIMEOUT => issued,
+        _ => return,
+    };
+
+    let summary = summarize_reply(msg);
+    info!("Sriracha answered {:?} with {:?}", issued.command, summary);
+    tracked.sauce_summaries.write().await.insert(
+        (msg.channel_id, issued.group),
+        SauceSummary {
+            command: issued.command,
+            summary,
+            seen_at: Timestamp::now(),
+        },
+    );
+}
+
+fn author_get(input: &str) -> IResult<&str, &str> {
+    let (input, _) = tag("Looking up ")(input)?;
+    // Titles like "Death by Daylight" contain " by " too, so the author
+    // starts after the last one.
+    let (input, author) = map_opt(not_line_ending, |line: &str| {
+        let (title, author) = line.rsplit_once(" by ")?;
+        let author = author.trim_end();
+        let author = strip_wrapping(author.strip_suffix('.').unwrap_or(author));
+        (!title.is_empty() && !author.is_empty()).then_some(author)
+    })(input)?;
+
+    Ok((input, author))
+}
+
+/// Splits "Alice, Bob." style author lists into the individual authors.
+fn split_authors(authors: &str) -> Vec<&str> {
+    authors
+        .split(',')
+        .map(|author| {
+            let author = author.trim();
+            strip_wrapping(author.strip_suffix('.').unwrap_or(author))
+        })
+        .filter(|author| !author.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod author_tests {
+    use super::*;
+
+    fn author(input: &str) -> Option<&str> {
+        author_get(input).ok().map(|(_, author)| author)
+    }
+
+    #[test]
+    fn parses_title_and_author() {
+        assert_eq!(author("Looking up Some Title by Author."), Some("Author"));
+        assert_eq!(
+            author("Looking up Some Title by Author Name."),
+            Some("Author Name")
+        );
+    }
+
+    #[test]
+    fn keeps_initials_in_the_author() {
+        assert_eq!(
+            author("Looking up Some Title by J. R. Smith."),
+            Some("J. R. Smith")
+        );
+        assert_eq!(
+            author("Looking up Some Title by J. Smith"),
+            Some("J. Smith")
+        );
+        assert_eq!(
+            author("Looking up Some Title by J. R. Smith"),
+            Some("J. R. Smith")
+        );
+    }
+
+    #[test]
+    fn strips_quotes_and_brackets_around_the_author() {
+        assert_eq!(
+            author("Looking up \"Title\" by [Author Name]."),
+            Some("Author Name")
+        );
+        assert_eq!(author("Looking up Title by \"Author\"."), Some("Author"));
+        assert_eq!(author("Looking up Title by “Author”"), Some("Author"));
+        assert_eq!(author("Looking up Title by ( Author )."), Some("Author"));
+        assert_eq!(strip_wrapping("'Author'"), "Author");
+        assert_eq!(strip_wrapping("[Author"), "[Author");
+        assert_eq!(strip_wrapping("Author (Artist)"), "Author (Artist)");
+    }
+
+    #[test]
+    fn rejects_a_missing_by() {
+        assert!(author_get("Looking up Some Title.").is_err());
+        assert!(author_get("Looking up Some Title byAuthor.").is_err());
+        assert!(author_get("Found Some Title by Author.").is_err());
+        assert!(author_get("Looking up  by Author.").is_err());
+    }
+
+    #[test]
+    fn accepts_a_missing_trailing_period() {
+        assert_eq!(author("Looking up Some Title by Author"), Some("Author"));
+    }
+
+    #[test]
+    fn trims_whitespace_around_the_author() {
+        assert_eq!(
+            author("Looking up Some Title by   Author  "),
+            Some("Author")
+        );
+        assert_eq!(
+            author("Looking up Some Title by  Author .  "),
+            Some("Author")
+        );
+        assert!(author_get("  Looking up Some Title by Author.").is_err());
+    }
+
+    #[test]
+    fn rejects_an_empty_author() {
+        assert!(author_get("Looking up Some Title by .").is_err());
+        assert!(author_get("Looking up Some Title by    ").is_err());
+    }
+
+    #[test]
+    fn takes_the_author_after_the_last_by() {
+        assert_eq!(
+            author("Looking up Death by Daylight by Author."),
+            Some("Author")
+        );
+        assert_eq!(
+            author("Looking up Stand by Me by J. R. Smith"),
+            Some("J. R. Smith")
+        );
+        assert_eq!(author("Looking up Death by Daylight by ."), None);
+    }
+
+    #[test]
+    fn splits_author_lists() {
+        assert_eq!(split_authors("Alice"), ["Alice"]);
+        assert_eq!(split_authors("Alice Smith."), ["Alice Smith"]);
+        assert_eq!(split_authors("Alice, Bob"), ["Alice", "Bob"]);
+        assert_eq!(
+            split_authors("Alice, [Bob], \"Carol\"."),
+            ["Alice", "Bob", "Carol"]
+        );
+        assert_eq!(split_authors("Alice., Bob.,"), ["Alice", "Bob"]);
+        assert_eq!(
+            author("Looking up Title by Alice, Bob.").map(split_authors),
+            Some(vec!["Alice", "Bob"])
+        );
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_line() {
+        assert_eq!(
+            author_get("Looking up Some Title by Author.\nnext line"),
+            Ok(("\nnext line", "Author"))
+        );
+    }
+}
+
+fn is_lookup_failure(reply: &Message) -> bool {
+    let content = reply.content.to_lowercase();
+    reply.embeds.is_empty() && (content.contains("not found") || content.contains("no results"))
+}
+
+async fn await_sauce_reply(
+    ctx: &Context,
+    channel_id: ChannelId,
+    config: &Config,
+) -> Option<Arc<Message>> {
+    let sriracha_bot_ids = config.sriracha_bot_ids();
+    channel_id
+        .await_reply(ctx)
+        .filter(move |reply| sriracha_bot_ids.contains(reply.author.id.as_u64()))
+        .timeout(SAUCE_REPLY_TIMEOUT)
+        .await
+}
+
+fn author_query(author: &str) -> String {
+    format!("-qa {author}")
+}
+
+/// Sends `sauce -qa` for `author`, retrying with exponential backoff while
+/// sriracha answers with a failure or doesn't answer at all.
+async fn lookup_author(ctx: &Context, msg: &Message, config: &Config, author: &str) {
+    let mut delay = config.author_lookup_delay;
+    let attempts = config.author_lookup_retries + 1;
+
+    for attempt in 1..=attempts {
+        sleep(delay).await;
+        if let Err(why) = send_sauce(ctx, msg, "author", &author_query(author)).await {
+            error!("Could not send author lookup: {:?}", why);
+            return;
+        }
+
+        if is_dry_run(ctx, msg.channel_id).await {
+            return;
+        }
+
+        match await_sauce_reply(ctx, msg.channel_id, config).await {
+            Some(reply) if !is_lookup_failure(&reply) => return,
+            Some(_) => warn!(
+                "Author lookup for {} failed, attempt {}/{}",
+                author, attempt, attempts
+            ),
+            None => warn!(
+                "Author lookup for {} got no reply, attempt {}/{}",
+                author, attempt, attempts
+            ),
+        }
+
+        delay *= 2;
+    }
+
+    let _ = reply(
+        ctx,
+        msg,
+        format!("Could not look up {author} after {attempts} attempt(s)"),
+    )
+    .await;
+}
+
+async fn register_slash_commands(ctx: &Context) -> serenity::Result<Vec<Command>> {
+    let max_id = current_config(ctx).await.max_id;
+    Command::set_global_application_commands(&ctx.http, |commands| {
+        for group in STAGES {
+            commands.create_application_command(|command| {
+                command
+                    .name(group)
+                    .description(format!("Run {group} stage commands"));
+                for (name, description) in [
+                    ("list", "List an item"),
+                    ("move", "Move an item to the next stage"),
+                    ("delete", "Delete an item"),
+                ] {
+                    command.create_option(|option| {
+                        option
+                            .name(name)
+                            .description(description)
+                            .kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|id| {
+                                id.name("id")
+                                    .description("Item id, defaults to 1")
+                                    .kind(CommandOptionType::Integer)
+                                    .min_int_value(1)
+                                    .max_int_value(max_id)
+                            });
+                        if name == "delete" {
+                            option.create_sub_option(|confirm| {
+                                confirm
+                                    .name("confirm")
+                                    .description("Confirm the delete")
+                                    .kind(CommandOptionType::Boolean)
+                            });
+                        }
+                        option
+                    });
+                }
+                command
+            });
+        }
+        commands
+    })
+    .await
+}
+
+async fn respond_ephemeral(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: impl ToString,
+) -> serenity::Result<()> {
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| message.content(content).ephemeral(true))
+        })
+        .await
+}
+
+async fn handle_slash_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> serenity::Result<()> {
+    let group = match STAGES.into_iter().find(|group| *group == command.data.name) {
+        Some(group) => group,
+        None => return Ok(()),
+    };
+    let subcommand = match command.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return Ok(()),
+    };
+    let option = |name: &str| {
+        subcommand
+            .options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_ref())
+    };
+    let id = option("id")
+        .and_then(|id| id.as_u64())
+        .map(|id| u32::try_from(id).unwrap_or(u32::MAX))
+        .unwrap_or(1);
+    let confirmed = option("confirm")
+        .and_then(|confirm| confirm.as_bool())
+        .unwrap_or(false);
+
+    let access = check_access(
+        ctx,
+        &format!("{group}_{}", subcommand.name),
+        command.user.id,
+        command.channel_id,
+        command.guild_id,
+        async { command.member.clone() },
+    )
+    .await;
+    if let Err(why) = access {
+        return respond_ephemeral(ctx, command, why).await;
+    }
+    let config = current_config(ctx).await;
+    let id = match check_id(id, config.max_id) {
+        Ok(id) => id,
+        Err(why) => return respond_ephemeral(ctx, command, why).await,
+    };
+
+    let StageConfig { stage, move_target } = config.stages[group];
+    let sauce = match subcommand.name.as_str() {
+        "list" => list_command(group, &config.column(stage), id),
+        "move" => move_command(&config.column(stage), id, &config.column(move_target)),
+        "delete" if config.confirm_deletes && !confirmed => {
+            return respond_ephemeral(ctx, command, "Set `confirm` to True to delete").await;
+        }
+        "delete" => delete_command(&config.column(stage), id),
+        _ => return Ok(()),
+    };
+    let sauce = sauce_command(&config.sauce_keyword, &sauce);
+
+    let cooldown = ctx
+        .data
+        .read()
+        .await
+        .get::<SlashCooldown>()
+        .expect("Expected SlashCooldown in TypeMap")
+        .clone();
+    if let Some(wait) = cooldown.wait_for(command.user.id).await {
+        return respond_ephemeral(
+            ctx,
+            command,
+            format!("Slow down, try again in {:.1}s", wait.as_secs_f64()),
+        )
+        .await;
+    }
+
+    relay(
+        ctx,
+        command.channel_id,
+        command.guild_id,
+        command.user.id,
+        group,
+        sauce.clone(),
+    )
+    .await?;
+    if let Some(counter) = metrics(ctx).await.stage_commands(group) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    respond_ephemeral(ctx, command, format!("Sent `{sauce}`")).await?;
+
+    if subcommand.name == "move" && !is_dry_run(ctx, command.channel_id).await {
+        if let Some(landed) = landed_id(ctx, command.channel_id, &config, move_target).await {
+            let last_move = LastMove {
+                stage,
+                ids: vec![landed],
+                target: move_target,
+            };
+            record_move(ctx, command.guild_id, group, last_move).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn keepalive(ctx: Context, interval: Duration, started_at: Instant) {
+    loop {
+        sleep(interval).await;
+
+        let latencies = match shard_manager(&ctx).await {
+            Some(shard_manager) => {
+                let manager = shard_manager.lock().await;
+                let runners = manager.runners.lock().await;
+                let mut latencies: Vec<_> = runners
+                    .iter()
+                    .map(|(id, runner)| match runner.latency {
+                        Some(latency) => {
+                            (id.0, format!("shard {} {}ms", id.0, latency.as_millis()))
+                        }
+                        None => (id.0, format!("shard {} latency not yet available", id.0)),
+                    })
+                    .collect();
+                latencies.sort_by_key(|(id, _)| *id);
+                latencies
+                    .into_iter()
+                    .map(|(_, latency)| latency)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+            None => "no shard manager".to_string(),
+        };
+        info!(
+            "Keepalive: up {}s, {}",
+            started_at.elapsed().as_secs(),
+            latencies
+        );
+    }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("Connected as {}", ready.user.name);
+        self.ready.store(true, Ordering::SeqCst);
+        let tracked = tracked_state(&ctx).await;
+        load_state(&ctx, &tracked).await;
+
+        if let Err(why) = register_slash_commands(&ctx).await {
+            error!("Could not register slash commands: {:?}", why);
+        }
+
+        // `ready` fires again after reconnects; only start one keepalive task.
+        if let Some(interval) = self.keepalive_interval {
+            if !self.keepalive_started.swap(true, Ordering::SeqCst) {
+                tokio::spawn(keepalive(ctx.clone(), interval, self.started_at));
+            }
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = interaction {
+            let _in_flight = InFlightGuard::start(&ctx).await;
+            if let Err(why) = handle_slash_command(&ctx, &command).await {
+                error!("Could not handle /{}: {:?}", command.data.name, why);
+            }
+        }
+    }
+
+    async fn resume(&self, ctx: Context, _: ResumedEvent) {
+        info!("Shard {} resumed", ctx.shard_id);
+    }
+
+    async fn shard_stage_update(&self, _: Context, event: ShardStageUpdateEvent) {
+        info!(
+            "Shard {} changed stage: {} -> {}",
+            event.shard_id.0, event.old, event.new
+        );
+    }
+
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let tracked = tracked_state(&ctx).await;
+        let is_tracked = tracked
+            .last_sriracha_embed_messages
+            .read()
+            .await
+            .get(&event.channel_id)
+            .is_some_and(|embeds| embeds.iter().any(|embed| embed.id == event.id));
+        if !is_tracked {
+            return;
+        }
+
+        // `new` is only filled in when the message cache had the original.
+        let message = match new {
+            Some(message) => message,
+            None => match event.channel_id.message(&ctx.http, event.id).await {
+                Ok(message) => message,
+                Err(why) => {
+                    warn!("Could not fetch edited embed {}: {:?}", event.id, why);
+                    return;
+                }
+            },
+        };
+        if message.embeds.is_empty() {
+            info!("Tracked embed {} lost its embed in an edit", message.id);
+        }
+        tracked.refresh_embed(message).await;
+        save_state(&tracked).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        if let Err(why) = advance_from_reaction(&ctx, &reaction).await {
+            warn!(
+                "Could not advance embed {} from a reaction: {:?}",
+                reaction.message_id, why
+            );
+        }
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.id == ctx.cache.current_user_id() {
+            return;
+        }
+
+        let config = current_config(&ctx).await;
+        let tracked = tracked_state(&ctx).await;
+
+        if config.is_sriracha_bot(&msg.author) {
+            if let Some(stage) = echoed_stage(&msg.content) {
+                {
+                    let mut pending_stage_commands = tracked.pending_stage_commands.write().await;
+                    pending_stage_commands.insert((msg.guild_id, stage), msg.id);
+                }
+
+                sleep(ECHO_DEBOUNCE).await;
+
+                {
+                    let pending_stage_commands = tracked.pending_stage_commands.read().await;
+                    let mut last_stage_commands = tracked.last_stage_commands.write().await;
+                    if !should_store_echo(
+                        pending_stage_commands.get(&(msg.guild_id, stage)).copied(),
+                        msg.id,
+                        last_stage_commands.get(&(msg.guild_id, stage)),
+                        &msg.content,
+                    ) {
+                        return;
+                    }
+                    last_stage_commands.insert((msg.guild_id, stage), msg.content.clone());
+                }
+                save_state(&tracked).await;
+            } else {
+                capture_summary(&tracked, &msg).await;
+                if msg.embeds.is_empty() {
+                    return;
+                }
+
+                let message_id = msg.id;
+                tracked.push_embed(msg).await;
+                info!("Last sriracha embed message: {}", message_id);
+                save_state(&tracked).await;
+            }
+        } else if config.is_lc_bot(&msg.author) && msg.content.starts_with("Looking up") {
+            {
+                let mut last_lookup_message_id = tracked.last_lookup_message_id.write().await;
+                if *last_lookup_message_id == Some(msg.id) {
+                    return;
+                }
+                *last_lookup_message_id = Some(msg.id);
+            }
+
+            match author_get(&msg.content) {
+                Ok((_, author)) => {
+                    {
+                        let mut last_author = tracked.last_author.write().await;
+                        last_author.insert(msg.guild_id, author.to_string());
+                    }
+
+                    metrics(&ctx)
+                        .await
+                        .lookups_succeeded
+                        .fetch_add(1, Ordering::Relaxed);
+
+                    {
+                        let mut pending_lookups = tracked.pending_lookups.lock().await;
+                        let starts_batch = !pending_lookups.contains_key(&msg.channel_id);
+                        let pending = pending_lookups.entry(msg.channel_id).or_default();
+                        for author in split_authors(author) {
+                            if !pending
+                                .iter()
+                                .any(|queued| queued.eq_ignore_ascii_case(author))
+                            {
+                                pending.push(author.to_string());
+                            }
+                        }
+                        if !starts_batch {
+                            // The message that started the batch sends it.
+                            return;
+                        }
+                    }
+
+                    sleep(LOOKUP_COALESCE_WINDOW).await;
+                    let authors = tracked
+                        .pending_lookups
+                        .lock()
+                        .await
+                        .remove(&msg.channel_id)
+                        .unwrap_or_default();
+                    for author in &authors {
+                        lookup_author(&ctx, &msg, &config, author).await;
+                    }
+                }
+                Err(why) => {
+                    warn!("Could not find author in {:?}: {:?}", msg.content, why);
+                    tracked
+                        .failed_lookups
+                        .write()
+                        .await
+                        .insert(msg.channel_id, msg.content.clone());
+                    metrics(&ctx)
+                        .await
+                        .lookups_failed
+                        .fetch_add(1, Ordering::Relaxed);
+
+                    let should_reply = {
+                        let mut last_author_errors = tracked.last_author_errors.write().await;
+                        let now = Instant::now();
+                        match last_author_errors.get(&msg.channel_id) {
+                            Some(last) if now.duration_since(*last) < AUTHOR_ERROR_COOLDOWN => {
+                                false
+                            }
+                            _ => {
+                                last_author_errors.insert(msg.channel_id, now);
+                                true
+                            }
+                        }
+                    };
+
+                    if should_reply {
+                        let _ = reply(&ctx, &msg, "Could not find author").await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites a leading alias from `COMMAND_ALIASES` into its command.
+fn resolve_alias(content: &str, prefix: &str, aliases: &HashMap<String, String>) -> Option<String> {
+    let rest = content.strip_prefix(prefix)?;
+    let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let command = aliases.get(&word.to_lowercase())?;
+
+    Some(format!("{prefix}{command} {args}").trim_end().to_string())
+}
+
+/// Words that start a stage command, e.g. the `move` in `*lc move 5`.
+const STAGE_COMMAND_WORDS: [&str; 8] = [
+    "move",
+    "moverange",
+    "undo",
+    "del",
+    "delet",
+    "delete",
+    "retry",
+    "swap",
+];
+
+/// Turns a bare stage command like `*move 5` or `*3-7` into one for `stage`.
+fn resolve_active_stage(content: &str, prefix: &str, stage: &str) -> Option<String> {
+    let rest = content.strip_prefix(prefix)?;
+    let word = rest.split_whitespace().next()?.to_lowercase();
+    let is_ids = word.starts_with(|c: char| c.is_ascii_digit());
+    if !is_ids && !STAGE_COMMAND_WORDS.contains(&word.as_str()) {
+        return None;
+    }
+
+    Some(format!("{prefix}{stage} {rest}"))
+}
+
+/// Runs messages through the alias table before the standard framework sees them.
+struct AliasFramework {
+    inner: StandardFramework,
+}
+
+#[async_trait]
+impl Framework for AliasFramework {
+    async fn dispatch(&self, ctx: Context, mut msg: Message) {
+        let prefix = {
+            let data = ctx.data.read().await;
+            data.get::<CommandPrefix>().cloned().unwrap_or_default()
+        };
+        // Covers the whole run, hooks included, so a panicking command is
+        // still counted as finished.
+        let _in_flight = if msg.content.starts_with(&prefix) {
+            Some(InFlightGuard::start(&ctx).await)
+        } else {
+            None
+        };
+        let config = current_config(&ctx).await;
+        if let Some(content) = resolve_alias(&msg.content, &prefix, &config.aliases) {
+            info!("Resolved alias {:?} to {:?}", msg.content, content);
+            msg.content = content;
+        }
+        if let Some(stage) = active_stage(&ctx, msg.channel_id).await {
+            if let Some(content) = resolve_active_stage(&msg.content, &prefix, stage) {
+                msg.content = content;
+            }
+        }
+
+        self.inner.dispatch(ctx, msg).await;
+    }
+}
+
+#[group]
+#[commands(
+    ping,
+    about,
+    shards,
+    upstream,
+    stats,
+    debug,
+    dryrun,
+    reload,
+    target,
+    author,
+    lang,
+    clearlang,
+    en,
+    jp,
+    status,
+    history,
+    sauceauthor,
+    reparse,
+    ack,
+    embed,
+    raw,
+    use_stage,
+    cancel,
+    rescan,
+    again,
+    stages,
+    lookup,
+    flush
+)]
+struct General;
+
+#[group]
+#[prefixes("lc", "l")]
+#[commands(lc_list, lc_move, lc_move_range, lc_undo, lc_delete, lc_retry, lc_swap)]
+struct Lc;
+
+#[group]
+#[prefixes("st", "s")]
+#[commands(st_list, st_move, st_move_range, st_undo, st_delete, st_retry, st_swap)]
+struct St;
+
+#[group]
+#[prefixes("qc", "q")]
+#[commands(qc_list, qc_move, qc_move_range, qc_undo, qc_delete, qc_retry, qc_swap)]
+struct Qc;
+
+#[group]
+#[prefix = "ts"]
+#[commands(ts_list, ts_move, ts_move_range, ts_undo, ts_delete, ts_retry, ts_swap)]
+struct Ts;
+
+#[hook]
+async fn before(ctx: &Context, msg: &Message, command_name: &str) -> bool {
+    let group = command_name.split('_').next().unwrap_or_default();
+    let access = check_access(
+        ctx,
+        command_name,
+        msg.author.id,
+        msg.channel_id,
+        msg.guild_id,
+        async { msg.member(ctx).await.ok() },
+    )
+    .await;
+    if let Err(why) = access {
+        let _ = reply(ctx, msg, why).await;
+        return false;
+    }
+
+    if let Some(counter) = metrics(ctx).await.stage_commands(group) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    true
+}
+
+/// Checks the channel allowlist and the roles `command_name` needs, for both
+/// prefix and slash commands. Owners may run anything anywhere. `member` is
+/// only looked up if the command needs a role.
+async fn check_access(
+    ctx: &Context,
+    command_name: &str,
+    user_id: UserId,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    member: impl std::future::Future<Output = Option<Member>>,
+) -> Result<(), &'static str> {
+    if is_owner(ctx, user_id).await {
+        return Ok(());
+    }
+    let config = current_config(ctx).await;
+    if !config.allows_command_in(command_name, channel_id) {
+        return Err("Sauce commands can't be used in this channel");
+    }
+    if let Some(roles) = config.required_roles(command_name) {
+        if !has_any_role(ctx, guild_id, member.await, roles).await {
+            return Err("Insufficient permissions");
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `member` has one of `roles`, given as role ids or names.
+async fn has_any_role(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    member: Option<Member>,
+    roles: &[String],
+) -> bool {
+    let (guild_id, member) = match (guild_id, member) {
+        (Some(guild_id), Some(member)) => (guild_id, member),
+        _ => return false,
+    };
+    if roles.iter().any(|role| {
+        role.parse()
+            .is_ok_and(|id| member.roles.contains(&RoleId(id)))
+    }) {
+        return true;
+    }
+
+    let guild_roles = match guild_id.roles(&ctx.http).await {
+        Ok(guild_roles) => guild_roles,
+        Err(why) => {
+            warn!("Could not fetch the guild roles: {:?}", why);
+            return false;
+        }
+    };
+    member
+        .roles
+        .iter()
+        .filter_map(|id| guild_roles.get(id))
+        .any(|role| {
+            roles
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&role.name))
+        })
+}
+
+#[hook]
+async fn after(ctx: &Context, msg: &Message, command_name: &str, result: CommandResult) {
+    if let Err(why) = result {
+        let notice = match why.downcast_ref::<CommandError>() {
+            Some(why) => why.to_string(),
+            None => {
+                error!(
+                    user_id = %msg.author.id,
+                    channel_id = %msg.channel_id,
+                    command = command_name,
+                    "Command failed: {:?}",
+                    why
+                );
+                "Something went wrong, check the logs".to_string()
+            }
+        };
+
+        match reply(ctx, msg, &notice).await {
+            Err(reply_error) if is_permission_error(&reply_error) => {
+                report_elsewhere(ctx, msg, command_name, &why).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_permission_error(why: &serenity::Error) -> bool {
+    match why {
+        serenity::Error::Model(ModelError::InvalidPermissions(_)) => true,
+        serenity::Error::Http(why) => matches!(
+            &**why,
+            HttpError::UnsuccessfulRequest(response)
+                if response.status_code == StatusCode::FORBIDDEN
+        ),
+        _ => false,
+    }
+}
+
+/// Posts a failure to `ERROR_CHANNEL_ID` when it couldn't be shown where the
+/// command was used.
+async fn report_elsewhere(ctx: &Context, msg: &Message, command_name: &str, why: &impl Display) {
+    let error_channel = match current_config(ctx).await.error_channel {
+        Some(error_channel) => error_channel,
+        None => {
+            warn!(
+                channel_id = %msg.channel_id,
+                "Could not post the error for {} and no error channel is set",
+                command_name
+            );
+            return;
+        }
+    };
+
+    let notice = format!(
+        "`{command_name}` from {} ({}) in <#{}> failed and I can't post there: {why}",
+        msg.author.tag(),
+        msg.author.id,
+        msg.channel_id
+    );
+    if let Err(fallback_error) = say(ctx, error_channel, notice).await {
+        error!(
+            channel_id = %error_channel,
+            "Could not post to the error channel either: {:?}",
+            fallback_error
+        );
+    }
+}
+
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError, _command_name: &str) {
+    match error {
+        DispatchError::OnlyForOwners => {
+            let _ = reply(ctx, msg, "Only the bot owner can use this command").await;
+        }
+        DispatchError::Ratelimited(info) if info.is_first_try => {
+            let _ = reply(
+                ctx,
+                msg,
+                format!(
+                    "Slow down, try again in {:.1}s",
+                    info.rate_limit.as_secs_f64()
+                ),
+            )
+            .await;
+        }
+        _ => {}
+    }
+}
+
+#[help]
+async fn help(
+    ctx: &Context,
+    msg: &Message,
+    _args: Args,
+    _help_options: &'static HelpOptions,
+    _groups: &[&'static CommandGroup],
+    _owners: HashSet<UserId>,
+) -> CommandResult {
+    let prefix = {
+        let data = ctx.data.read().await;
+        data.get::<CommandPrefix>().cloned().unwrap_or_default()
+    };
+    let config = current_config(ctx).await;
+
+    let mut fields = Vec::new();
+    for group in ["qc", "st", "lc", "ts"] {
+        let StageConfig { stage, move_target } = config.stages[group];
+        let stage = match config.columns.get(group) {
+            Some(column) => format!("{stage}, column {column}"),
+            None => stage.to_string(),
+        };
+        let name = match group {
+            "qc" | "st" | "lc" => {
+                format!("{prefix}{group} or {prefix}{} (stage {stage})", &group[..1])
+            }
+            _ => format!("{prefix}{group} (stage {stage})"),
+        };
+        let group = format!("{prefix}{group}");
+        fields.push((
+            name,
+            format!(
+                "`{group} [ids]` list items, e.g. `{group} 3`, `{group} 3-7` or `{group} 3 7 12`\n\
+                 `{group} all` list the whole stage\n\
+                 `{group} move [ids] [to <stage>] [-- note]` move items, to stage {move_target} by default; \
+                 `{group} move 5 2` moves item 5 to stage 2\n\
+                 `{group} moverange <range> <stage>` move a range of items\n\
+                 `{group} del|delet|delete [ids]` delete items, `{prefix}cancel` aborts the confirmation\n\
+                 `{group} undo` undo the last move\n\
+                 `{group} swap <id> <id>` swap two items\n\
+                 `{group} retry [peek]` resend (or just show) the last command sriracha echoed"
+            ),
+            false,
+        ));
+    }
+
+    let ack = if acks_enabled(ctx, msg.channel_id).await {
+        "on"
+    } else {
+        "off"
+    };
+    let advance = match &config.advance_emoji {
+        Some(emoji) => format!("React with {emoji} on an embed to move its item on\n"),
+        None => String::new(),
+    };
+    fields.push((
+        "General".to_string(),
+        format!(
+            "`{prefix}lang <code> [n]` flag the last (or nth last) embed, or the embed you reply to; \
+             `{prefix}en` and `{prefix}jp` are shortcuts\n\
+             `{prefix}lang clear` or `{prefix}clearlang` remove the language flags\n\
+             `{prefix}target <message id>` pick the embed flags go on\n\
+             `{prefix}rescan` find the latest embed again, e.g. after a restart\n\
+             `{prefix}author [retry]` show or look up the last author again\n\
+             `{prefix}sauceauthor <name>` look up an author\n\
+             `{prefix}reparse` retry the last author that could not be parsed\n\
+             `{prefix}lookup <message link or id>` look up the author of an old \"Looking up\" message\n\
+             `{prefix}status [id]` list the top of every stage\n\
+             `{prefix}stages` show which sauce stage each group uses\n\
+             `{prefix}history [@user]` show recent sauce commands\n\
+             `{prefix}again` resend the last sauce command in this channel, except moves and deletes\n\
+             `{prefix}dryrun [on|off]` show sauce commands instead of sending them\n\
+             `{prefix}ack [on|off]` confirm each sent command, currently {ack} here\n\
+             `{prefix}use [stage|none]` let `{prefix}3`, `{prefix}move 5` etc. act on a stage\n\
+             {advance}\
+             `{prefix}ping` check the bot's latency\n\
+             `{prefix}about` show the version and uptime\n\
+             Owner only: `{prefix}reload`, `{prefix}shards`, `{prefix}stats [reset]`, \
+             `{prefix}debug state`, `{prefix}flush`, `{prefix}embed dump`, `{prefix}raw <command>`, \
+             `{prefix}upstream`"
+        ),
+        false,
+    ));
+
+    send_queued(ctx, msg.channel_id, || {
+        msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("lc-streamliner")
+                    .description("The pipeline runs qc → st → lc → ts. Ids default to 1.")
+                    .fields(fields.clone())
+            })
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Reads the token from the file at `DISCORD_TOKEN_FILE` when set, e.g. a
+/// mounted secret, otherwise from `DISCORD_TOKEN`.
+fn read_token() -> String {
+    match env::var("DISCORD_TOKEN_FILE") {
+        Ok(path) => std::fs::read_to_string(&path)
+            .unwrap_or_else(|why| panic!("Could not read DISCORD_TOKEN_FILE {path:?}: {why}"))
+            .trim()
+            .to_string(),
+        Err(_) => env::var("DISCORD_TOKEN").expect("Expected a token in environment"),
+    }
+}
+
+/// Serves `/healthz` on `port` for container health checks: 200 once `ready`
+/// has fired and every shard is connected, 503 until then.
+async fn serve_health(port: u16, ready: Arc<AtomicBool>, shard_manager: ShardManagerSlot) {
+    let make_service = make_service_fn(move |_| {
+        let (ready, shard_manager) = (ready.clone(), shard_manager.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                health(request, ready.clone(), shard_manager.clone())
+            }))
+        }
+    });
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(why) => {
+            error!("Could not bind the health endpoint to {}: {:?}", addr, why);
+            return;
+        }
+    };
+    info!("Serving /healthz on {}", addr);
+    if let Err(why) = server.serve(make_service).await {
+        error!("Health endpoint stopped: {:?}", why);
+    }
+}
+
+async fn health(
+    request: Request<Body>,
+    ready: Arc<AtomicBool>,
+    shard_manager: ShardManagerSlot,
+) -> Result<Response<Body>, Infallible> {
+    if request.uri().path() != "/healthz" {
+        return Ok(Response::builder()
+            .status(404)
+            .body(Body::empty())
+            .expect("Expected a valid response"));
+    }
+
+    let shard_manager = shard_manager.read().await.clone();
+    let connected = match shard_manager {
+        Some(shard_manager) => {
+            let manager = shard_manager.lock().await;
+            let runners = manager.runners.lock().await;
+            !runners.is_empty()
+                && runners
+                    .values()
+                    .all(|runner| runner.stage == ConnectionStage::Connected)
+        }
+        None => false,
+    };
+    let (status, body) = if ready.load(Ordering::SeqCst) && connected {
+        (200, "ok")
+    } else {
+        (503, "not ready")
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .body(Body::from(body))
+        .expect("Expected a valid response"))
+}
+
+/// Errors another connect attempt can't fix: a bad token, or intents the bot
+/// isn't allowed or that don't exist.
+fn is_fatal_connect_error(why: &serenity::Error) -> bool {
+    matches!(
+        why,
+        serenity::Error::Gateway(
+            GatewayError::InvalidAuthentication
+                | GatewayError::InvalidGatewayIntents
+                | GatewayError::DisallowedGatewayIntents
+        )
+    )
+}
+
+/// Sets up logging filtered by `RUST_LOG`, as plain text or, with
+/// `LOG_FORMAT=json`, one JSON object per line for log pipelines.
+fn init_logging() {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+    let format = env::var("LOG_FORMAT").map(|format| format.trim().to_lowercase());
+    match format.as_deref() {
+        Ok("json") => builder.json().init(),
+        Ok("text") | Ok("") | Err(_) => builder.init(),
+        Ok(format) => {
+            builder.init();
+            warn!("Unknown LOG_FORMAT {:?}, expected text or json", format);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // Taken before `.env` is applied, so `*reload` can tell the two apart.
+    let process_env: EnvVars = env::vars().collect();
+    let env_file = dotenv::dotenv().expect("Failed to load .env file");
+    init_logging();
+
+    let vars: EnvVars = env::vars().collect();
+    let file = ConfigFile::load(&vars).unwrap_or_else(|why| panic!("{why}"));
+    // Settings that only apply at startup, so `*reload` leaves them alone.
+
+    let sauce_cooldown: u64 = parse_env(&vars, "SAUCE_COOLDOWN_SECS", file.sauce_cooldown_secs)
+        .unwrap_or_else(|why| panic!("{why}"));
+    // 0 turns the keepalive log off.
+    let keepalive_secs: u64 = parse_env(
+        &vars,
+        "KEEPALIVE_INTERVAL_SECS",
+        file.keepalive_interval_secs,
+    )
+    .unwrap_or_else(|why| panic!("{why}"));
+    // 0 leaves the health endpoint off.
+    let health_port: u16 =
+        parse_env(&vars, "HEALTH_PORT", file.health_port).unwrap_or_else(|why| panic!("{why}"));
+    // Presences are a privileged intent that has to be enabled for the bot first.
+    let track_presences: bool = parse_env(&vars, "TRACK_PRESENCES", file.track_presences)
+        .unwrap_or_else(|why| panic!("{why}"));
+    let connect_retries: u32 = parse_env(&vars, "CONNECT_RETRIES", file.connect_retries)
+        .unwrap_or_else(|why| panic!("{why}"));
+    let shutdown_grace = Duration::from_secs(
+        parse_env(&vars, "SHUTDOWN_GRACE_SECS", file.shutdown_grace_secs)
+            .unwrap_or_else(|why| panic!("{why}")),
+    );
+    let prefix = env_var(&vars, "COMMAND_PREFIX").unwrap_or_else(|_| file.command_prefix.clone());
+    assert!(
+        !prefix.trim().is_empty(),
+        "Expected COMMAND_PREFIX to not be empty"
+    );
+    let config = Config::from_file(file, &vars).unwrap_or_else(|why| panic!("{why}"));
+
+    let token = read_token();
+    info!(
+        "Starting with prefix '{}', sauce cooldown {}s, {}",
+        prefix,
+        sauce_cooldown,
+        config.summary()
+    );
+    let http = Http::new(&token);
+
+    let (owners, _bot_id) = match http.get_current_application_info().await {
+        Ok(info) => {
+            let mut owners = HashSet::new();
+            owners.insert(info.owner.id);
+
+            (owners, info.id)
+        }
+        Err(why) => panic!("Could not access app info: {:?}", why),
+    };
+
+    let mut intents = GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS;
+    if track_presences {
+        intents |= GatewayIntents::GUILDS | GatewayIntents::GUILD_PRESENCES;
+    }
+    let started_at = Instant::now();
+    let ready = Arc::new(AtomicBool::new(false));
+    let keepalive_started = Arc::new(AtomicBool::new(false));
+    let shard_manager: ShardManagerSlot = Arc::new(RwLock::new(None));
+    let shutting_down = Arc::new(AtomicBool::new(false));
+
+    // State that outlives a failed connect, handed to the client of every attempt.
+    let config = Arc::new(RwLock::new(Arc::new(config)));
+    let tracked = Arc::new(TrackedState::default());
+    let startup_env = Arc::new(StartupEnv {
+        env_file,
+        process_env,
+    });
+    let metrics = Arc::new(Metrics::default());
+    let dry_run_channels = Arc::new(RwLock::new(HashSet::new()));
+    let ack_channels = Arc::new(RwLock::new(HashSet::new()));
+    let active_stages = Arc::new(RwLock::new(HashMap::new()));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let slash_cooldown = Arc::new(SlashCooldown::new(Duration::from_secs(sauce_cooldown)));
+
+    if health_port > 0 {
+        tokio::spawn(serve_health(
+            health_port,
+            ready.clone(),
+            shard_manager.clone(),
+        ));
+    }
+
+    {
+        let (in_flight, shard_manager, shutting_down) = (
+            in_flight.clone(),
+            shard_manager.clone(),
+            shutting_down.clone(),
+        );
+        tokio::spawn(async move {
+            tokio::signal::ctrl_c()
+                .await
+                .expect("could not register ctrl+c handler");
+
+            let deadline = Instant::now() + shutdown_grace;
+            while in_flight.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+                sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+            match in_flight.load(Ordering::SeqCst) {
+                0 => info!("No commands running, shutting down"),
+                running => warn!("Shutting down with {} command(s) still running", running),
+            }
+
+            shutting_down.store(true, Ordering::SeqCst);
+            if let Some(shard_manager) = shard_manager.read().await.clone() {
+                shard_manager.lock().await.shutdown_all().await;
+            }
+        });
+    }
+
+    let attempts = connect_retries + 1;
+    let mut delay = CONNECT_RETRY_DELAY;
+    for attempt in 1..=attempts {
+        if shutting_down.load(Ordering::SeqCst) {
+            return;
+        }
+        info!("Connecting, attempt {}/{}", attempt, attempts);
+        // A client can't be started twice, so every attempt gets a new one.
+        let framework = StandardFramework::new()
+            .configure(|c| c.owners(owners.clone()).prefix(&prefix))
+            .before(before)
+            .after(after)
+            .on_dispatch_error(dispatch_error)
+            .bucket("sauce", |b| b.delay(sauce_cooldown))
+            .await
+            .help(&HELP)
+            .group(&GENERAL_GROUP)
+            .group(&LC_GROUP)
+            .group(&ST_GROUP)
+            .group(&QC_GROUP)
+            .group(&TS_GROUP);
+        let mut client = Client::builder(&token, intents)
+            .framework(AliasFramework { inner: framework })
+            .event_handler(Handler {
+                started_at,
+                keepalive_interval: (keepalive_secs > 0)
+                    .then(|| Duration::from_secs(keepalive_secs)),
+                keepalive_started: keepalive_started.clone(),
+                ready: ready.clone(),
+            })
+            .await
+            .expect("Error creating client");
+        {
+            let mut data = client.data.write().await;
+            data.insert::<ShardManagerContainer>(shard_manager.clone());
+            data.insert::<ConfigContainer>(config.clone());
+            data.insert::<TrackedStateContainer>(tracked.clone());
+            data.insert::<CommandPrefix>(prefix.clone());
+            data.insert::<BotOwners>(owners.clone());
+            data.insert::<PresencesEnabled>(track_presences);
+            data.insert::<StartedAt>(started_at);
+            data.insert::<StartupEnv>(startup_env.clone());
+            data.insert::<MetricsContainer>(metrics.clone());
+            data.insert::<DryRunChannels>(dry_run_channels.clone());
+            data.insert::<AckChannels>(ack_channels.clone());
+            data.insert::<ActiveStages>(active_stages.clone());
+            data.insert::<InFlightCommands>(in_flight.clone());
+            data.insert::<SlashCooldown>(slash_cooldown.clone());
+        }
+        *shard_manager.write().await = Some(client.shard_manager.clone());
+
+        match client.start().await {
+            Ok(()) => return,
+            Err(why) if is_fatal_connect_error(&why) => {
+                error!("Client error: {:?}, not retrying", why);
+                return;
+            }
+            Err(why) if attempt < attempts => {
+                error!("Client error: {:?}, retrying in {}s", why, delay.as_secs());
+                sleep(delay).await;
+                delay *= 2;
+            }
+            Err(why) => error!("Client error: {:?}, giving up", why),
+        }
+    }
+}
+
+async fn send_sauce(
+    ctx: &Context,
+    msg: &Message,
+    group: &str,
+    command: &str,
+) -> serenity::Result<Message> {
+    let keyword = current_config(ctx).await.sauce_keyword.clone();
+    send_command(ctx, msg, group, sauce_command(&keyword, command)).await
+}
+
+async fn send_command(
+    ctx: &Context,
+    msg: &Message,
+    group: &str,
+    command: String,
+) -> serenity::Result<Message> {
+    let sent = relay(
+        ctx,
+        msg.channel_id,
+        msg.guild_id,
+        msg.author.id,
+        group,
+        command.clone(),
+    )
+    .await?;
+    // Automatic author lookups pass the upstream bot's message; only ack people.
+    if !msg.author.bot
+        && acks_enabled(ctx, msg.channel_id).await
+        && !is_dry_run(ctx, msg.channel_id).await
+    {
+        reply(ctx, msg, format!("Sent `{command}`")).await?;
+    }
+
+    Ok(sent)
+}
+
+/// Moves the item on a tracked embed when someone allowed to move it reacts
+/// with `ADVANCE_EMOJI`, as if they had sent `*<group> move <id>`.
+async fn advance_from_reaction(ctx: &Context, reaction: &Reaction) -> serenity::Result<()> {
+    let config = current_config(ctx).await;
+    if !config
+        .advance_emoji
+        .as_ref()
+        .is_some_and(|emoji| same_emoji(emoji, &reaction.emoji))
+    {
+        return Ok(());
+    }
+    let user_id = match reaction.user_id {
+        Some(user_id) if user_id != ctx.cache.current_user_id() => user_id,
+        _ => return Ok(()),
+    };
+
+    let tracked = tracked_state(ctx).await;
+    let item = tracked
+        .last_sriracha_embed_messages
+        .read()
+        .await
+        .get(&reaction.channel_id)
+        .and_then(|embeds| embeds.iter().find(|embed| embed.id == reaction.message_id))
+        .and_then(|embed| {
+            reply_lines(embed).iter().find_map(|line| {
+                let (_, (stage, id)) = item_get(line).ok()?;
+                Some((stage.to_string(), id))
+            })
+        });
+    let (column, id) = match item {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+    let (group, stage, target) = match STAGES.into_iter().find_map(|group| {
+        config
+            .stages
+            .get(group)
+            .filter(|stages| {
+                column == stages.stage.to_string() || column == config.column(stages.stage)
+            })
+            .map(|stages| (group, stages.stage, stages.move_target))
+    }) {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    if !config.allows_move(stage, target) {
+        info!("Ignoring advance of {stage}#{id}: moves to {target} are not allowed");
+        return Ok(());
+    }
+
+    if !is_owner(ctx, user_id).await {
+        if !config.allows_channel(reaction.channel_id) {
+            return Ok(());
+        }
+        if let Some(roles) = config.required_roles(&format!("{group}_move")) {
+            let member = match reaction.guild_id {
+                Some(guild_id) => guild_id.member(ctx, user_id).await.ok(),
+                None => None,
+            };
+            if !has_any_role(ctx, reaction.guild_id, member, roles).await {
+                info!(user_id = %user_id, "Ignoring advance reaction without a move role");
+                return Ok(());
+            }
+        }
+    }
+
+    {
+        let embeds = tracked.last_sriracha_embed_messages.read().await;
+        let mut advanced = tracked.advanced_embeds.lock().await;
+        advanced.retain(|message_id| {
+            embeds
+                .values()
+                .any(|embeds| embeds.iter().any(|embed| embed.id == *message_id))
+        });
+        if !advanced.insert(reaction.message_id) {
+            return Ok(());
+        }
+    }
+
+    let command = sauce_command(
+        &config.sauce_keyword,
+        &move_command(&config.column(stage), id, &config.column(target)),
+    );
+    relay(
+        ctx,
+        reaction.channel_id,
+        reaction.guild_id,
+        user_id,
+        group,
+        command,
+    )
+    .await?;
+    if is_dry_run(ctx, reaction.channel_id).await {
+        return Ok(());
+    }
+    if let Some(landed) = landed_id(ctx, reaction.channel_id, &config, target).await {
+        let last_move = LastMove {
+            stage,
+            ids: vec![landed],
+            target,
+        };
+        record_move(ctx, reaction.guild_id, group, last_move).await;
+    }
+
+    Ok(())
+}
+
+async fn relay(
+    ctx: &Context,
+    channel_id: ChannelId,
+    guild_id: GuildKey,
+    user_id: UserId,
+    group: &str,
+    command: String,
+) -> serenity::Result<Message> {
+    info!(
+        user_id = %user_id,
+        channel_id = %channel_id,
+        group,
+        command = %command,
+        "Sending sauce command"
+    );
+
+    if is_dry_run(ctx, channel_id).await {
+        return say(ctx, channel_id, format!("Dry run:\n```\n{command}\n```")).await;
+    }
+
+    let sent = say(ctx, channel_id, &command).await?;
+    if let Some(group) = STAGES.into_iter().find(|stage| *stage == group) {
+        tracked_state(ctx)
+            .await
+            .issued_commands
+            .write()
+            .await
+            .insert(
+                channel_id,
+                IssuedCommand {
+                    group,
+                    command: command.clone(),
+                    sent_at: Instant::now(),
+                },
+            );
+    }
+    tracked_state(ctx)
+        .await
+        .last_sent
+        .write()
+        .await
+        .insert((guild_id, channel_id), (group.to_string(), command.clone()));
+    record_history(ctx, user_id, command).await;
+
+    Ok(sent)
+}
+
+async fn record_history(ctx: &Context, user_id: UserId, command: String) {
+    let tracked = tracked_state(ctx).await;
+    let mut history = tracked.history.write().await;
+    if history.len() == HISTORY_CAPACITY {
+        history.pop_front();
+    }
+    history.push_back(HistoryEntry {
+        sent_at: Timestamp::now(),
+        user_id,
+        command,
+    });
+}
+
+async fn say(
+    ctx: &Context,
+    channel_id: ChannelId,
+    content: impl Display,
+) -> serenity::Result<Message> {
+    send_queued(ctx, channel_id, || channel_id.say(&ctx.http, &content)).await
+}
+
+/// Answers the invoking message, so busy channels show which command a
+/// reply belongs to.
+async fn reply(ctx: &Context, msg: &Message, content: impl Display) -> serenity::Result<Message> {
+    send_queued(ctx, msg.channel_id, || msg.reply(ctx, &content)).await
+}
+
+/// Sends one message at a time per channel, at least `send_interval` apart,
+/// so batches of ids don't run into Discord's rate limits.
+async fn send_queued<F, Fut>(
+    ctx: &Context,
+    channel_id: ChannelId,
+    send: F,
+) -> serenity::Result<Message>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = serenity::Result<Message>>,
+{
+    let slot = tracked_state(ctx)
+        .await
+        .send_slots
+        .lock()
+        .await
+        .entry(channel_id)
+        .or_insert_with(|| Arc::new(Mutex::new(Instant::now())))
+        .clone();
+    // Holding the slot for the whole send keeps the channel's messages in order.
+    let mut next_send = slot.lock().await;
+    sleep(next_send.saturating_duration_since(Instant::now())).await;
+
+    let mut result = with_send_timeout(ctx, channel_id, send()).await;
+    if is_rate_limited(&result) {
+        // Serenity already waits out 429s it knows about, so this is rare.
+        warn!(
+            channel_id = %channel_id,
+            "Rate limited sending a message, retrying in {}ms",
+            RATE_LIMIT_BACKOFF.as_millis()
+        );
+        sleep(RATE_LIMIT_BACKOFF).await;
+        result = with_send_timeout(ctx, channel_id, send()).await;
+    }
+    *next_send = Instant::now() + current_config(ctx).await.send_interval;
+
+    result
+}
+
+fn is_rate_limited<T>(result: &serenity::Result<T>) -> bool {
+    match result {
+        Err(serenity::Error::Http(why)) => matches!(
+            &**why,
+            HttpError::UnsuccessfulRequest(response)
+                if response.status_code == StatusCode::TOO_MANY_REQUESTS
+        ),
+        _ => false,
+    }
+}
+
+async fn with_send_timeout(
+    ctx: &Context,
+    channel_id: ChannelId,
+    send: impl std::future::Future<Output = serenity::Result<Message>>,
+) -> serenity::Result<Message> {
+    let limit = current_config(ctx).await.send_timeout;
+    match timeout(limit, send).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!(
+                channel_id = %channel_id,
+                "Timed out sending a message after {}ms",
+                limit.as_millis()
+            );
+            Err(serenity::Error::Other("Timed out sending a message"))
+        }
+    }
+}
+
+// The sauce command strings, kept free of serenity types so `command_tests`
+// can check them.
+
+fn sauce_command(keyword: &str, command: &str) -> String {
+    format!("{keyword} {command}")
+}
+
+// Stages are passed as columns, e.g. `3` or `C` with `COLUMN_LETTERS`.
+
+fn list_command(group: &str, stage: &str, id: u32) -> String {
+    match group {
+        "lc" => format!("lc {stage}#{id}"),
+        _ => format!("{stage}#{id}"),
+    }
+}
+
+/// Lists a whole stage, e.g. `3` or `lc 3` with the default template.
+fn list_all_command(group: &str, template: &str, stage: &str) -> String {
+    let command = template.replace("{stage}", stage);
+    match group {
+        "lc" => format!("lc {command}"),
+        _ => command,
+    }
+}
+
+fn move_command(stage: &str, id: u32, target: &str) -> String {
+    format!("move {stage}#{id} {target}")
+}
+
+/// Moves an item to another position within its stage.
+fn reorder_command(stage: &str, id: u32, position: u32) -> String {
+    format!("move {stage}#{id} {stage}#{position}")
+}
+
+fn delete_command(stage: &str, id: u32) -> String {
+    format!("delete {stage}#{id}")
+}
+
+#[cfg(test)]
+mod command_tests {
+    use super::*;
+
+    fn config(file: ConfigFile) -> Config {
+        Config::from_file(file, &EnvVars::new()).unwrap()
+    }
+
+    #[test]
+    fn lists_lc_with_its_prefix() {
+        assert_eq!(list_command("lc", "3", 5), "lc 3#5");
+        assert_eq!(list_command("st", "2", 5), "2#5");
+        assert_eq!(list_command("qc", "1", 5), "1#5");
+        assert_eq!(list_command("ts", "5", 5), "5#5");
+        assert_eq!(
+            sauce_command("sauce", &list_command("lc", "3", 5)),
+            "sauce lc 3#5"
+        );
+    }
+
+    #[test]
+    fn lists_ids_and_ranges() {
+        let lists: Vec<_> = parse_ids(&[], 999)
+            .unwrap()
+            .into_iter()
+            .map(|id| list_command("st", "2", id))
+            .collect();
+        assert_eq!(lists, ["2#1"]);
+
+        let lists: Vec<_> = parse_ids(&["3-5".to_string(), "9".to_string()], 999)
+            .unwrap()
+            .into_iter()
+            .map(|id| list_command("lc", "3", id))
+            .collect();
+        assert_eq!(lists, ["lc 3#3", "lc 3#4", "lc 3#5", "lc 3#9"]);
+    }
+
+    #[test]
+    fn lists_a_whole_stage() {
+        assert!(is_list_all(Some("all")));
+        assert!(is_list_all(Some("*")));
+        assert!(!is_list_all(Some("3")));
+        assert!(!is_list_all(None));
+        assert_eq!(list_all_command("lc", "{stage}", "3"), "lc 3");
+        assert_eq!(list_all_command("qc", "{stage}", "1"), "1");
+        assert_eq!(list_all_command("st", "{stage}#1-999", "2"), "2#1-999");
+    }
+
+    #[test]
+    fn moves_reorders_and_deletes() {
+        assert_eq!(move_command("3", 5, "4"), "move 3#5 4");
+        assert_eq!(move_command("2", 7, "1"), "move 2#7 1");
+        assert_eq!(reorder_command("3", 7, 3), "move 3#7 3#3");
+        assert_eq!(delete_command("1", 12), "delete 1#12");
+    }
+
+    #[test]
+    fn writes_stage_numbers_by_default() {
+        let config = config(ConfigFile::default());
+        assert_eq!(config.column(3), "3");
+        assert_eq!(config.column(4), "4");
+    }
+
+    #[test]
+    fn writes_column_letters_when_enabled() {
+        let config = config(ConfigFile {
+            column_letters: true,
+            columns: HashMap::from([("ts".to_string(), "x".to_string())]),
+            ..ConfigFile::default()
+        });
+        assert_eq!(config.column(3), "C");
+        assert_eq!(config.column(4), "D");
+        assert_eq!(config.column(5), "X");
+        assert_eq!(config.column(30), "30");
+        assert_eq!(
+            move_command(&config.column(3), 5, &config.column(4)),
+            "move C#5 D"
+        );
+        assert_eq!(list_command("lc", &config.column(3), 5), "lc C#5");
+        assert_eq!(delete_command(&config.column(1), 2), "delete A#2");
+    }
+}
+
+/// A mistake in how a command was used. Returning one from a command gets
+/// its message sent back to the channel by the `after` hook.
+#[derive(Debug)]
+enum CommandError {
+    /// A required argument was left out; holds the usage hint.
+    MissingArg(&'static str),
+    BadNumber(String),
+    OutOfRange {
+        id: u32,
+        max_id: u32,
+    },
+    BadRange(String),
+    TooManyIds,
+    /// There is nothing tracked for the command to act on.
+    NoState(String),
+    Invalid(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::MissingArg(usage) => write!(f, "{usage}"),
+            CommandError::BadNumber(input) => write!(f, "Expected a number, got '{input}'"),
+            CommandError::OutOfRange { id, max_id } => {
+                write!(f, "Expected an id from 1 to {max_id}, got {id}")
+            }
+            CommandError::BadRange(input) => {
+                write!(f, "Invalid range '{input}': start must not exceed end")
+            }
+            CommandError::TooManyIds => write!(f, "At most {MAX_IDS} ids can be sent at once"),
+            CommandError::NoState(what) => write!(f, "{what}"),
+            CommandError::Invalid(why) => write!(f, "{why}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+fn parse_number<T: FromStr>(input: &str) -> Result<T, CommandError> {
+    input
+        .parse()
+        .map_err(|_| CommandError::BadNumber(input.to_string()))
+}
+
+fn check_id(id: u32, max_id: u32) -> Result<u32, CommandError> {
+    if id == 0 || id > max_id {
+        return Err(CommandError::OutOfRange { id, max_id });
+    }
+
+    Ok(id)
+}
+
+fn parse_id(input: &str, max_id: u32) -> Result<u32, CommandError> {
+    check_id(parse_number(input)?, max_id)
+}
+
+fn parse_id_range(input: &str, max_id: u32) -> Result<RangeInclusive<u32>, CommandError> {
+    match input.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_id(start, max_id)?, parse_id(end, max_id)?);
+            if start > end {
+                return Err(CommandError::BadRange(input.to_string()));
+            }
+
+            Ok(start..=end)
+        }
+        None => parse_id(input, max_id).map(|id| id..=id),
+    }
+}
+
+fn check_id_count(existing: usize, range: &RangeInclusive<u32>) -> Result<(), CommandError> {
+    if existing + (range.end() - range.start()) as usize >= MAX_IDS {
+        return Err(CommandError::TooManyIds);
+    }
+
+    Ok(())
+}
+
+fn get_ids(mut args: Args, max_id: u32) -> Result<Vec<u32>, CommandError> {
+    let inputs = args
+        .iter::<String>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|why| CommandError::Invalid(why.to_string()))?;
+
+    parse_ids(&inputs, max_id)
+}
+
+/// Parses ids and ranges like `3 5-7`, defaulting to id 1 when there are none.
+fn parse_ids(inputs: &[String], max_id: u32) -> Result<Vec<u32>, CommandError> {
+    if inputs.is_empty() {
+        return Ok(vec![1]);
+    }
+
+    let mut ids = Vec::new();
+    for input in inputs {
+        let range = parse_id_range(input, max_id)?;
+        check_id_count(ids.len(), &range)?;
+        ids.extend(range);
+    }
+
+    Ok(ids)
+}
+
+/// Whether a list command's first argument asks for the whole stage.
+fn is_list_all(input: Option<&str>) -> bool {
+    matches!(input, Some("all" | "*"))
+}
+
+async fn send_list(ctx: &Context, msg: &Message, args: Args, group: &str) -> CommandResult {
+    let config = current_config(ctx).await;
+    let StageConfig { stage, .. } = config.stages[group];
+    let list_all = is_list_all(args.current());
+    let commands: Vec<_> = if list_all {
+        vec![list_all_command(
+            group,
+            &config.list_all,
+            &config.column(stage),
+        )]
+    } else {
+        get_ids(args, config.max_id)?
+            .into_iter()
+            .map(|id| list_command(group, &config.column(stage), id))
+            .collect()
+    };
+
+    for command in &commands {
+        send_sauce(ctx, msg, group, command).await?;
+    }
+
+    // Only a single list has one reply we can tell apart from the others.
+    if commands.len() == 1 && !is_dry_run(ctx, msg.channel_id).await {
+        if let Some(reply) = await_sauce_reply(ctx, msg.channel_id, &config).await {
+            paginate_reply(ctx, msg, &config, &reply).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn reply_lines(reply: &Message) -> Vec<String> {
+    let mut lines = Vec::new();
+    for embed in &reply.embeds {
+        if let Some(description) = &embed.description {
+            lines.extend(description.lines().map(str::to_string));
+        }
+        for field in &embed.fields {
+            lines.push(format!("**{}** {}", field.name, field.value));
+        }
+    }
+    if lines.is_empty() {
+        lines.extend(reply.content.lines().map(str::to_string));
+    }
+    lines.retain(|line| !line.trim().is_empty());
+
+    lines
+}
+
+/// Re-posts a long upstream reply as an embed with one page per
+/// `list_page_size` lines, flipped with the page reactions.
+async fn paginate_reply(
+    ctx: &Context,
+    msg: &Message,
+    config: &Config,
+    reply: &Message,
+) -> serenity::Result<()> {
+    let pages: Vec<String> = reply_lines(reply)
+        .chunks(config.list_page_size)
+        .map(|lines| lines.join("\n"))
+        .collect();
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let title = reply
+        .embeds
+        .first()
+        .and_then(|embed| embed.title.clone())
+        .unwrap_or_else(|| "Results".to_string());
+    let view = send_queued(ctx, msg.channel_id, || {
+        msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(&title)
+                    .description(&pages[0])
+                    .footer(|f| f.text(format!("Page 1/{}", pages.len())))
+            })
+        })
+    })
+    .await?;
+    for emoji in [PREVIOUS_PAGE_EMOJI, NEXT_PAGE_EMOJI] {
+        view.react(ctx, ReactionType::Unicode(emoji.to_string()))
+            .await?;
+    }
+
+    // The command is done once the first page is up; flipping through the
+    // rest shouldn't hold up shutdown for `PAGE_TIMEOUT`.
+    let (ctx, author_id) = (ctx.clone(), msg.author.id);
+    tokio::spawn(async move {
+        if let Err(why) = flip_pages(&ctx, author_id, view, &title, &pages).await {
+            warn!("Could not flip list pages: {:?}", why);
+        }
+    });
+
+    Ok(())
+}
+
+/// Turns the pages of `view` as `author_id` reacts, until `PAGE_TIMEOUT`
+/// passes without a reaction.
+async fn flip_pages(
+    ctx: &Context,
+    author_id: UserId,
+    mut view: Message,
+    title: &str,
+    pages: &[String],
+) -> serenity::Result<()> {
+    let mut page = 0;
+    while let Some(reaction) = view
+        .await_reaction(ctx)
+        .author_id(author_id)
+        .timeout(PAGE_TIMEOUT)
+        .filter(|reaction| {
+            reaction.emoji.unicode_eq(PREVIOUS_PAGE_EMOJI)
+                || reaction.emoji.unicode_eq(NEXT_PAGE_EMOJI)
+        })
+        .await
+    {
+        let reaction = reaction.as_inner_ref();
+        page = if reaction.emoji.unicode_eq(NEXT_PAGE_EMOJI) {
+            (page + 1) % pages.len()
+        } else {
+            (page + pages.len() - 1) % pages.len()
+        };
+        // Needs Manage Messages; without it the user just unreacts themselves.
+        let _ = reaction.delete(ctx).await;
+
+        view.edit(ctx, |m| {
+            m.embed(|e| {
+                e.title(title)
+                    .description(&pages[page])
+                    .footer(|f| f.text(format!("Page {}/{}", page + 1, pages.len())))
+            })
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn parse_move_target(input: &str, config: &Config, stage: u32) -> Result<u32, CommandError> {
+    let target = input
+        .parse()
+        .map_err(|_| CommandError::Invalid(format!("Expected a stage number, got '{input}'")))?;
+    if target == stage || !config.is_known_stage(target) {
+        return Err(CommandError::Invalid(format!(
+            "Cannot move items to stage {target}"
+        )));
+    }
+    if !config.allows_move(stage, target) {
+        let mut allowed: Vec<_> = config
+            .transitions
+            .iter()
+            .filter(|(from, _)| *from == stage)
+            .map(|(_, to)| to.to_string())
+            .collect();
+        allowed.sort();
+        return Err(CommandError::Invalid(format!(
+            "Moving from stage {stage} to stage {target} is not allowed, allowed targets: {}",
+            if allowed.is_empty() {
+                "none".to_string()
+            } else {
+                allowed.join(", ")
+            }
+        )));
+    }
+
+    Ok(target)
+}
+
+/// Reads the ids and target of a move: `5`, `5 2`, `3-7 4`, `3 7 12` or
+/// `3 7 12 to 4` (also `->4`). Two plain arguments are an id and a target, so
+/// several ids going somewhere other than the default need `to`.
+fn get_move_args(
+    mut args: Args,
+    config: &Config,
+    stage: u32,
+    default_target: u32,
+) -> Result<(Vec<u32>, u32), CommandError> {
+    const USAGE: CommandError =
+        CommandError::MissingArg("Expected a stage after `to`, e.g. `3 7 12 to 4`");
+
+    let inputs = args
+        .iter::<String>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|why| CommandError::Invalid(why.to_string()))?;
+    let (ids, target) = match inputs
+        .iter()
+        .position(|input| input == "to" || input.starts_with("->"))
+    {
+        Some(at) => {
+            let (target, rest) = match inputs[at].strip_prefix("->") {
+                Some(target) if !target.is_empty() => (target, &inputs[at + 1..]),
+                _ => (
+                    inputs.get(at + 1).ok_or(USAGE)?.as_str(),
+                    inputs.get(at + 2..).unwrap_or_default(),
+                ),
+            };
+            if !rest.is_empty() {
+                return Err(CommandError::Invalid(format!(
+                    "Expected nothing after the target stage, got '{}'",
+                    rest.join(" ")
+                )));
+            }
+            (&inputs[..at], Some(target))
+        }
+        None if inputs.len() == 2 => (&inputs[..1], Some(inputs[1].as_str())),
+        None => (&inputs[..], None),
+    };
+
+    let ids = parse_ids(ids, config.max_id)?;
+    let target = match target {
+        Some(input) => parse_move_target(input, config, stage)?,
+        None => default_target,
+    };
+
+    Ok((ids, target))
+}
+
+fn get_move_range_args(
+    mut args: Args,
+    config: &Config,
+    stage: u32,
+) -> Result<(RangeInclusive<u32>, u32), CommandError> {
+    const USAGE: CommandError =
+        CommandError::MissingArg("Expected a range and a target stage, e.g. `3-7 4`");
+
+    let ids = parse_id_range(&args.single::<String>().map_err(|_| USAGE)?, config.max_id)?;
+    check_id_count(0, &ids)?;
+    let target = parse_move_target(&args.single::<String>().map_err(|_| USAGE)?, config, stage)?;
+
+    Ok((ids, target))
+}
+
+async fn issue_moves(
+    ctx: &Context,
+    msg: &Message,
+    group: &str,
+    stage: u32,
+    ids: &[u32],
+    target: u32,
+) -> serenity::Result<Option<Vec<u32>>> {
+    let config = current_config(ctx).await;
+    let (from, to) = (config.column(stage), config.column(target));
+    // Where each item landed, or `None` once one of them can't be told.
+    let mut landed = (!is_dry_run(ctx, msg.channel_id).await).then(Vec::new);
+    for id in highest_first(ids) {
+        send_sauce(ctx, msg, group, &move_command(&from, id, &to)).await?;
+        if landed.is_some() {
+            let found = landed_id(ctx, msg.channel_id, &config, target).await;
+            if found.is_none() {
+                info!("Could not tell where {from}#{id} landed, so the move can't be undone");
+            }
+            landed = landed.zip(found).map(|(mut landed, id)| {
+                landed.push(id);
+                landed
+            });
+        }
+    }
+
+    Ok(landed)
+}
+
+/// Where a moved item ended up in `target`, read from sriracha's reply to the move.
+async fn landed_id(
+    ctx: &Context,
+    channel_id: ChannelId,
+    config: &Config,
+    target: u32,
+) -> Option<u32> {
+    let reply = await_sauce_reply(ctx, channel_id, config).await?;
+    find_landed_id(&reply_lines(&reply), &config.column(target), target)
+}
+
+/// The first item in `target`, written either as `column` or as the stage number.
+fn find_landed_id(lines: &[String], column: &str, target: u32) -> Option<u32> {
+    lines.iter().find_map(|line| {
+        let (_, (stage, id)) = item_get(line).ok()?;
+        (stage == column || stage == target.to_string()).then_some(id)
+    })
+}
+
+/// Orders ids for sending moves or deletes one by one. Items after a removed
+/// one shift up, so going from the highest id down leaves the ones still to
+/// send where they were.
+fn highest_first(ids: &[u32]) -> Vec<u32> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    ids.dedup();
+    ids
+}
+
+#[cfg(test)]
+mod move_tests {
+    use super::*;
+
+    #[test]
+    fn sends_the_highest_id_first() {
+        assert_eq!(highest_first(&[3, 4, 5, 6, 7]), [7, 6, 5, 4, 3]);
+        assert_eq!(highest_first(&[12, 3, 7, 3]), [12, 7, 3]);
+        assert_eq!(highest_first(&[1]), [1]);
+    }
+}
+
+async fn record_move(ctx: &Context, guild_id: GuildKey, group: &'static str, last_move: LastMove) {
+    let tracked = tracked_state(ctx).await;
+    let mut last_moves = tracked.last_moves.write().await;
+    last_moves.insert((guild_id, group), last_move);
+}
+
+/// Splits a trailing note off move arguments, e.g. `5 4 -- needs redraw`.
+fn split_note(input: &str) -> (&str, Option<&str>) {
+    let delimiter = input.match_indices("--").map(|(at, _)| at).find(|&at| {
+        (at == 0 || input[..at].ends_with(char::is_whitespace))
+            && input[at + 2..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+    });
+
+    match delimiter {
+        Some(at) => {
+            let note = input[at + 2..].trim();
+            (&input[..at], (!note.is_empty()).then_some(note))
+        }
+        None => (input, None),
+    }
+}
+
+/// Takes the note off `args`, leaving only the ids and target to parse.
+fn take_note(args: Args) -> (Args, Option<String>) {
+    let (rest, note) = split_note(args.rest());
+    (
+        Args::new(rest, &[Delimiter::Single(' ')]),
+        note.map(ToString::to_string),
+    )
+}
+
+/// Posts a move's note separately, so it never ends up in the sauce command.
+async fn post_note(ctx: &Context, msg: &Message, note: Option<String>) -> serenity::Result<()> {
+    if let Some(note) = note {
+        say(
+            ctx,
+            msg.channel_id,
+            format!("Note from {}: {note}", msg.author.name),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn send_move(ctx: &Context, msg: &Message, args: Args, group: &'static str) -> CommandResult {
+    let config = current_config(ctx).await;
+    let StageConfig { stage, move_target } = config.stages[group];
+    let (args, note) = take_note(args);
+    let (ids, target) = get_move_args(args, &config, stage, move_target)?;
+
+    if let Some(ids) = issue_moves(ctx, msg, group, stage, &ids, target).await? {
+        record_move(ctx, msg.guild_id, group, LastMove { stage, ids, target }).await;
+    }
+    post_note(ctx, msg, note).await?;
+
+    Ok(())
+}
+
+async fn send_move_range(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+    group: &'static str,
+) -> CommandResult {
+    let config = current_config(ctx).await;
+    let StageConfig { stage, .. } = config.stages[group];
+    let (args, note) = take_note(args);
+    let (ids, target) = get_move_range_args(args, &config, stage)?;
+
+    let ids: Vec<_> = ids.collect();
+    let count = ids.len();
+    if let Some(ids) = issue_moves(ctx, msg, group, stage, &ids, target).await? {
+        record_move(ctx, msg.guild_id, group, LastMove { stage, ids, target }).await;
+    }
+    reply(
+        ctx,
+        msg,
+        format!(
+            "Issued {count} move(s) from stage {} to stage {}",
+            config.column(stage),
+            config.column(target)
+        ),
+    )
+    .await?;
+    post_note(ctx, msg, note).await?;
+
+    Ok(())
+}
+
+fn get_swap_args(mut args: Args, max_id: u32) -> Result<(u32, u32), CommandError> {
+    const USAGE: CommandError = CommandError::MissingArg("Expected two ids to swap, e.g. `3 5`");
+
+    let a = parse_id(&args.single::<String>().map_err(|_| USAGE)?, max_id)?;
+    let b = parse_id(&args.single::<String>().map_err(|_| USAGE)?, max_id)?;
+    if a == b {
+        return Err(CommandError::Invalid(format!(
+            "Cannot swap item {a} with itself"
+        )));
+    }
+
+    Ok((a.min(b), a.max(b)))
+}
+
+/// Sauce has no swap, so it's done as two moves within the stage: the later
+/// item moves up into the earlier slot, pushing the earlier item down by one,
+/// which then moves into the slot the later item left.
+async fn send_swap(ctx: &Context, msg: &Message, args: Args, group: &str) -> CommandResult {
+    let config = current_config(ctx).await;
+    let StageConfig { stage, .. } = config.stages[group];
+    let (first, second) = get_swap_args(args, config.max_id)?;
+
+    let stage = config.column(stage);
+    let commands = [
+        reorder_command(&stage, second, first),
+        reorder_command(&stage, first + 1, second),
+    ];
+    for command in &commands {
+        send_sauce(ctx, msg, group, command).await?;
+    }
+    reply(
+        ctx,
+        msg,
+        format!(
+            "Swapped {stage}#{first} and {stage}#{second} with `{}`",
+            commands.join("` and `")
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn undo_move(ctx: &Context, msg: &Message, group: &'static str) -> CommandResult {
+    let last_move = tracked_state(ctx)
+        .await
+        .last_moves
+        .write()
+        .await
+        .remove(&(msg.guild_id, group));
+
+    match last_move {
+        Some(LastMove { stage, ids, target }) => {
+            if !current_config(ctx).await.allows_move(target, stage) {
+                return Err(CommandError::Invalid(format!(
+                    "Moving from stage {target} back to stage {stage} is not allowed"
+                ))
+                .into());
+            }
+            issue_moves(ctx, msg, group, target, &ids, stage).await?;
+        }
+        None => {
+            return Err(CommandError::NoState(format!("There is no {group} move to undo")).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists ids with consecutive runs collapsed, e.g. `3-5, 9`, cut off after
+/// `DELETE_PREVIEW_RUNS` runs.
+fn id_summary(ids: &[u32]) -> String {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &id in ids {
+        match runs.last_mut() {
+            Some((_, end)) if id == *end + 1 => *end = id,
+            _ => runs.push((id, id)),
+        }
+    }
+
+    let mut summary: Vec<_> = runs
+        .iter()
+        .take(DELETE_PREVIEW_RUNS)
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect();
+    if runs.len() > DELETE_PREVIEW_RUNS {
+        let hidden: u32 = runs[DELETE_PREVIEW_RUNS..]
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .sum();
+        summary.push(format!("and {hidden} more"));
+    }
+    summary.join(", ")
+}
+
+async fn confirm_delete(
+    ctx: &Context,
+    msg: &Message,
+    stage: u32,
+    ids: &[u32],
+) -> Result<bool, serenity::Error> {
+    let config = current_config(ctx).await;
+    let stage = config.column(stage);
+    let target = match ids {
+        [id] => format!("{stage}#{id}"),
+        _ => format!(
+            "{} items from stage {stage}: {}",
+            ids.len(),
+            id_summary(ids)
+        ),
+    };
+    let (confirm, cancel) = (config.confirm_emoji.clone(), config.cancel_emoji.clone());
+    let prompt = reply(
+        ctx,
+        msg,
+        format!("Delete {target}? React with {confirm} to confirm or {cancel} to cancel"),
+    )
+    .await?;
+    prompt.react(ctx, confirm.clone()).await?;
+    prompt.react(ctx, cancel.clone()).await?;
+
+    let tracked = tracked_state(ctx).await;
+    let (cancel_sender, cancelled) = oneshot::channel();
+    // A newer delete from the same user replaces this one, which drops the
+    // sender and so cancels it as well.
+    tracked
+        .pending_deletes
+        .lock()
+        .await
+        .insert((msg.channel_id, msg.author.id), cancel_sender);
+
+    let collector = {
+        let (confirm, cancel) = (confirm.clone(), cancel.clone());
+        prompt
+            .await_reaction(ctx)
+            .author_id(msg.author.id)
+            .timeout(DELETE_CONFIRM_TIMEOUT)
+            .filter(move |reaction| {
+                same_emoji(&reaction.emoji, &confirm) || same_emoji(&reaction.emoji, &cancel)
+            })
+    };
+    let reaction = tokio::select! {
+        reaction = collector => reaction,
+        _ = cancelled => None,
+    };
+    tracked
+        .pending_deletes
+        .lock()
+        .await
+        .retain(|_, sender| !sender.is_closed());
+
+    let confirmed = match reaction {
+        Some(reaction) => same_emoji(&reaction.as_inner_ref().emoji, &confirm),
+        None => false,
+    };
+    if !confirmed {
+        reply(ctx, msg, "Delete cancelled").await?;
+    }
+
+    Ok(confirmed)
+}
+
+async fn send_delete(ctx: &Context, msg: &Message, args: Args, group: &str) -> CommandResult {
+    let config = current_config(ctx).await;
+    let StageConfig { stage, .. } = config.stages[group];
+    let ids = get_ids(args, config.max_id)?;
+
+    if config.confirm_deletes && !confirm_delete(ctx, msg, stage, &ids).await? {
+        return Ok(());
+    }
+
+    let stage = config.column(stage);
+    for id in highest_first(&ids) {
+        send_sauce(ctx, msg, group, &delete_command(&stage, id)).await?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("")]
+async fn lc_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_list(ctx, msg, args, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("move")]
+async fn lc_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move(ctx, msg, args, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("moverange")]
+async fn lc_move_range(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move_range(ctx, msg, args, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("undo")]
+async fn lc_undo(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    undo_move(ctx, msg, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("del", "delet", "delete")]
+async fn lc_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_delete(ctx, msg, args, "lc").await
+}
+
+async fn last_stage_command(ctx: &Context, msg: &Message, stage: &'static str) -> Option<String> {
+    tracked_state(ctx)
+        .await
+        .last_stage_commands
+        .read()
+        .await
+        .get(&(msg.guild_id, stage))
+        .cloned()
+        .filter(|command| !command.trim().is_empty())
+}
+
+async fn retry_last(ctx: &Context, msg: &Message, stage: &'static str) -> CommandResult {
+    match last_stage_command(ctx, msg, stage).await {
+        Some(retried_message) => {
+            send_command(ctx, msg, stage, retried_message).await?;
+        }
+        None => {
+            return Err(
+                CommandError::NoState(format!("No previous {stage} command to retry")).into(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows what `retry` would send, without sending it.
+async fn peek_last(ctx: &Context, msg: &Message, stage: &'static str) -> CommandResult {
+    match last_stage_command(ctx, msg, stage).await {
+        Some(command) => {
+            reply(ctx, msg, format!("```\n{command}\n```")).await?;
+        }
+        None => {
+            return Err(
+                CommandError::NoState(format!("No previous {stage} command to retry")).into(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("retry")]
+#[sub_commands(lc_retry_peek)]
+async fn lc_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    retry_last(ctx, msg, "lc").await
+}
+
+#[command]
+#[aliases("peek")]
+async fn lc_retry_peek(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    peek_last(ctx, msg, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("swap")]
+async fn lc_swap(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_swap(ctx, msg, args, "lc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("")]
+async fn st_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_list(ctx, msg, args, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("move")]
+async fn st_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move(ctx, msg, args, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("moverange")]
+async fn st_move_range(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move_range(ctx, msg, args, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("undo")]
+async fn st_undo(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    undo_move(ctx, msg, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("del", "delet", "delete")]
+async fn st_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_delete(ctx, msg, args, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("retry")]
+#[sub_commands(st_retry_peek)]
+async fn st_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    retry_last(ctx, msg, "st").await
+}
+
+#[command]
+#[aliases("peek")]
+async fn st_retry_peek(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    peek_last(ctx, msg, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("swap")]
+async fn st_swap(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_swap(ctx, msg, args, "st").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("")]
+async fn qc_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_list(ctx, msg, args, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("move")]
+async fn qc_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move(ctx, msg, args, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("moverange")]
+async fn qc_move_range(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move_range(ctx, msg, args, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("undo")]
+async fn qc_undo(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    undo_move(ctx, msg, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("del", "delet", "delete")]
+async fn qc_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_delete(ctx, msg, args, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("retry")]
+#[sub_commands(qc_retry_peek)]
+async fn qc_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    retry_last(ctx, msg, "qc").await
+}
+
+#[command]
+#[aliases("peek")]
+async fn qc_retry_peek(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    peek_last(ctx, msg, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("swap")]
+async fn qc_swap(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_swap(ctx, msg, args, "qc").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("")]
+async fn ts_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_list(ctx, msg, args, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("move")]
+async fn ts_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move(ctx, msg, args, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("moverange")]
+async fn ts_move_range(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_move_range(ctx, msg, args, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("undo")]
+async fn ts_undo(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    undo_move(ctx, msg, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("del", "delet", "delete")]
+async fn ts_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_delete(ctx, msg, args, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("retry")]
+#[sub_commands(ts_retry_peek)]
+async fn ts_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    retry_last(ctx, msg, "ts").await
+}
+
+#[command]
+#[aliases("peek")]
+async fn ts_retry_peek(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    peek_last(ctx, msg, "ts").await
+}
+
+#[command]
+#[bucket = "sauce"]
+#[aliases("swap")]
+async fn ts_swap(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_swap(ctx, msg, args, "ts").await
+}
+
+async fn shard_manager(ctx: &Context) -> Option<Arc<Mutex<ShardManager>>> {
+    let slot = ctx
+        .data
+        .read()
+        .await
+        .get::<ShardManagerContainer>()?
+        .clone();
+    let shard_manager = slot.read().await.clone();
+    shard_manager
+}
+
+#[command]
+#[bucket = "sauce"]
+async fn status(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let config = current_config(ctx).await;
+    let id = match args.single::<String>() {
+        Ok(input) => parse_id(&input, config.max_id)?,
+        Err(_) => config.status_id,
+    };
+
+    let mut groups = STAGES;
+    groups.sort_by_key(|group| config.stages[group].stage);
+    for (i, group) in groups.into_iter().enumerate() {
+        if i > 0 {
+            sleep(STATUS_SEND_DELAY).await;
+        }
+        let stage = config.column(config.stages[group].stage);
+        send_sauce(ctx, msg, group, &list_command(group, &stage, id)).await?;
+    }
+
+    let tracked = tracked_state(ctx).await;
+    let summaries = tracked.sauce_summaries.read().await;
+    let lines: Vec<_> = groups
+        .into_iter()
+        .filter_map(|group| {
+            let summary = summaries.get(&(msg.channel_id, group))?;
+            Some(format!(
+                "{group} <t:{}:R> `{}`: {}",
+                summary.seen_at.unix_timestamp(),
+                summary.command,
+                summary.summary
+            ))
+        })
+        .collect();
+    drop(summaries);
+    if !lines.is_empty() {
+        reply(
+            ctx,
+            msg,
+            format!("Last results seen here:\n{}", lines.join("\n")),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Formats a duration as e.g. `2d 3h 4m 5s`, leaving out leading zero units.
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let units = [
+        (secs / 86400, "d"),
+        (secs / 3600 % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let first = units
+        .iter()
+        .position(|(value, _)| *value > 0)
+        .unwrap_or(units.len() - 1);
+    units[first..]
+        .iter()
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[command]
+async fn about(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let started_at = ctx.data.read().await.get::<StartedAt>().copied();
+    let uptime = started_at.map_or("unknown".to_string(), |started_at| {
+        format_uptime(started_at.elapsed())
+    });
+    // Set GIT_COMMIT when building to have it show up here.
+    let commit = option_env!("GIT_COMMIT").unwrap_or("unknown");
+
+    send_queued(ctx, msg.channel_id, || {
+        msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("lc-streamliner")
+                    .field("Version", env!("CARGO_PKG_VERSION"), true)
+                    .field("Commit", commit, true)
+                    .field("Uptime", &uptime, true)
+            })
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+async fn ping(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let shard_manager = match shard_manager(ctx).await {
+        Some(shard_manager) => shard_manager,
+        None => {
+            reply(ctx, msg, "Could not get the shard manager").await?;
+            return Ok(());
+        }
+    };
+
+    let latency = {
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+        runners
+            .get(&ShardId(ctx.shard_id))
+            .and_then(|runner| runner.latency)
+    };
+
+    let mut response = match latency {
+        Some(latency) => format!("Pong! Heartbeat latency: {}ms", latency.as_millis()),
+        None => "Pong! Latency not yet available".to_string(),
+    };
+    if is_dry_run(ctx, msg.channel_id).await {
+        response.push_str("\nDry run is on in this channel");
+    }
+    reply(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+async fn shards(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let shard_manager = match shard_manager(ctx).await {
+        Some(shard_manager) => shard_manager,
+        None => {
+            reply(ctx, msg, "Could not get the shard manager").await?;
+            return Ok(());
+        }
+    };
+
+    let mut lines: Vec<_> = {
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+        runners
+            .iter()
+            .map(|(id, runner)| {
+                let latency = match runner.latency {
+                    Some(latency) => format!("{}ms", latency.as_millis()),
+                    None => "latency not yet available".to_string(),
+                };
+                (
+                    id.0,
+                    format!("Shard {}: {} ({latency})", id.0, runner.stage),
+                )
+            })
+            .collect()
+    };
+    lines.sort_by_key(|(id, _)| *id);
+
+    let response = if lines.is_empty() {
+        "No shards are running".to_string()
+    } else {
+        lines
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+async fn upstream(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => {
+            return Err(CommandError::Invalid(
+                "Upstream bots can only be checked in a server".to_string(),
+            )
+            .into())
+        }
+    };
+
+    let presences_enabled = ctx
+        .data
+        .read()
+        .await
+        .get::<PresencesEnabled>()
+        .copied()
+        .unwrap_or(false);
+    let presences = if presences_enabled {
+        ctx.cache
+            .guild_field(guild_id, |guild| guild.presences.clone())
+    } else {
+        None
+    };
+
+    let config = current_config(ctx).await;
+    let mut bots: Vec<_> = config.bots.iter().collect();
+    bots.sort();
+
+    let mut lines = Vec::new();
+    for (name, id) in bots {
+        let user_id = UserId(*id);
+        let status = if guild_id.member(ctx, user_id).await.is_err() {
+            "not in this server"
+        } else {
+            match &presences {
+                // Discord leaves offline members out of the presence list.
+                Some(presences) => presences
+                    .get(&user_id)
+                    .map_or("offline", |presence| presence.status.name()),
+                None => "in this server, status unknown",
+            }
+        };
+        lines.push(format!("{name}: {status}"));
+    }
+    if presences.is_none() {
+        lines.push(
+            "Presence data isn't available, set TRACK_PRESENCES=true and enable the presence intent"
+                .to_string(),
+        );
+    }
+    reply(ctx, msg, lines.join("\n")).await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[sub_commands(stats_reset)]
+async fn stats(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let metrics = metrics(ctx).await;
+    let lines: Vec<_> = metrics
+        .counters()
+        .into_iter()
+        .map(|(name, counter)| format!("{name}: {}", counter.load(Ordering::Relaxed)))
+        .collect();
+    reply(ctx, msg, lines.join("\n")).await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[aliases("reset")]
+async fn stats_reset(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    for (_, counter) in metrics(ctx).await.counters() {
+        counter.store(0, Ordering::Relaxed);
+    }
+    reply(ctx, msg, "Stats reset").await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[sub_commands(debug_state)]
+async fn debug(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    reply(ctx, msg, "Expected a subcommand, e.g. `debug state`").await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[sub_commands(embed_dump)]
+async fn embed(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    reply(ctx, msg, "Expected a subcommand, e.g. `embed dump`").await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[aliases("dump")]
+async fn embed_dump(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let last_embed = tracked_state(ctx)
+        .await
+        .last_sriracha_embed_messages
+        .read()
+        .await
+        .get(&msg.channel_id)
+        .and_then(|embeds| embeds.front().cloned());
+    let last_embed = match last_embed {
+        Some(last_embed) => last_embed,
+        None => return Err(CommandError::NoState("No embed tracked".to_string()).into()),
+    };
+
+    let mut lines = vec![format!("message {}", last_embed.id)];
+    for embed in &last_embed.embeds {
+        lines.push(format!(
+            "title: {}",
+            embed.title.as_deref().unwrap_or("<none>")
+        ));
+        lines.push(format!(
+            "description: {}",
+            embed.description.as_deref().unwrap_or("<none>")
+        ));
+        for field in &embed.fields {
+            lines.push(format!("field: {}", field.name));
+        }
+    }
+
+    // Keep the dump inside its code block and under Discord's message limit.
+    let mut dump = lines.join("\n").replace("```", "'''");
+    if dump.len() > EMBED_DUMP_LIMIT {
+        let end = (0..=EMBED_DUMP_LIMIT)
+            .rev()
+            .find(|end| dump.is_char_boundary(*end))
+            .unwrap_or(0);
+        dump.truncate(end);
+        dump.push_str("\n...");
+    }
+    reply(ctx, msg, format!("```\n{dump}\n```")).await?;
+
+    Ok(())
+}
+
+/// Forwards anything after `raw` to sriracha as-is, for upstream commands
+/// that don't have a wrapper here yet.
+#[command]
+#[owners_only]
+async fn raw(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let command = args.rest().trim();
+    if command.is_empty() {
+        return Err(
+            CommandError::MissingArg("Expected a sauce command, e.g. `raw list lc`").into(),
+        );
+    }
+
+    info!(user_id = %msg.author.id, command, "Forwarding raw sauce command");
+    send_sauce(ctx, msg, "raw", command).await?;
+
+    Ok(())
+}
+
+#[command]
+async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_id = match msg.mentions.first() {
+        Some(user) => user.id,
+        None => match args.single::<String>() {
+            Ok(input) => UserId(parse_number(&input)?),
+            Err(_) => msg.author.id,
+        },
+    };
+
+    if user_id != msg.author.id && !is_owner(ctx, msg.author.id).await {
+        reply(ctx, msg, "Only the bot owner can view other users' history").await?;
+        return Ok(());
+    }
+
+    let tracked = tracked_state(ctx).await;
+    let history = tracked.history.read().await;
+    let mut lines: Vec<_> = history
+        .iter()
+        .rev()
+        .filter(|entry| entry.user_id == user_id)
+        .take(HISTORY_SHOWN)
+        .map(|entry| {
+            format!(
+                "<t:{}:T> `{}`",
+                entry.sent_at.unix_timestamp(),
+                entry.command
+            )
+        })
+        .collect();
+    drop(history);
+
+    if lines.is_empty() {
+        reply(ctx, msg, format!("No sauce commands from <@{user_id}>")).await?;
+        return Ok(());
+    }
+
+    lines.reverse();
+    reply(
+        ctx,
+        msg,
+        format!(
+            "Last sauce commands from <@{user_id}>:\n{}",
+            lines.join("\n")
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[aliases("state")]
+async fn debug_state(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let config = current_config(ctx).await;
+    let tracked = tracked_state(ctx).await;
+
+    let mut lines = Vec::new();
+    {
+        let last_stage_commands = tracked.last_stage_commands.read().await;
+        for stage in STAGES {
+            let command = last_stage_commands
+                .get(&(msg.guild_id, stage))
+                .map(String::as_str)
+                .unwrap_or("<none>");
+            lines.push(format!("last {stage}: {command}"));
+        }
+    }
+
+    match tracked
+        .last_sriracha_embed_messages
+        .read()
+        .await
+        .get(&msg.channel_id)
+        .and_then(|embeds| Some((embeds.front()?.id, embeds.len())))
+    {
+        Some((embed_id, count)) => lines.push(format!(
+            "last embed: message {embed_id} in channel {} ({count} tracked)",
+            msg.channel_id
+        )),
+        None => lines.push("last embed: <none>".to_string()),
+    }
+
+    let mut bots: Vec<_> = config.bots.iter().collect();
+    bots.sort();
+    for (name, id) in bots {
+        lines.push(format!("{name} bot: {id}"));
+    }
+
+    reply(ctx, msg, format!("```\n{}\n```", lines.join("\n"))).await?;
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+async fn flush(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let tracked = tracked_state(ctx).await;
+    let cleared = tracked.flush().await;
+    save_state(&tracked).await;
+
+    let response = if cleared.is_empty() {
+        "Nothing was tracked".to_string()
+    } else {
+        format!("Cleared {}", cleared.join(", "))
+    };
+    reply(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+async fn dryrun(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let dry_run_channels = {
+        let data = ctx.data.read().await;
+        data.get::<DryRunChannels>()
+            .expect("Expected DryRunChannels in TypeMap")
+            .clone()
+    };
+
+    let response = match args.single::<String>().ok().as_deref() {
+        Some("on") => {
+            dry_run_channels.write().await.insert(msg.channel_id);
+            "Dry run is now on in this channel"
+        }
+        Some("off") => {
+            dry_run_channels.write().await.remove(&msg.channel_id);
+            "Dry run is now off in this channel"
+        }
+        Some(_) => "Expected `on` or `off`",
+        None if dry_run_channels.read().await.contains(&msg.channel_id) => {
+            "Dry run is on in this channel"
+        }
+        None => "Dry run is off in this channel",
+    };
+    reply(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+async fn stages(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let config = current_config(ctx).await;
+    let mut lines = vec![
+        format!(
+            "{:<6} {:>5} {:>11} {:>6}",
+            "group", "stage", "move target", "column"
+        ),
+        format!("{:-<6} {:->5} {:->11} {:->6}", "", "", "", ""),
+    ];
+    for group in STAGES {
+        let StageConfig { stage, move_target } = config.stages[group];
+        // Without `COLUMN_LETTERS` the column is just the stage number.
+        let column = config.column(stage);
+        lines.push(format!(
+            "{group:<6} {stage:>5} {move_target:>11} {column:>6}"
+        ));
+    }
+    reply(ctx, msg, format!("```\n{}\n```", lines.join("\n"))).await?;
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "sauce"]
+async fn again(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let last_sent = tracked_state(ctx)
+        .await
+        .last_sent
+        .read()
+        .await
+        .get(&(msg.guild_id, msg.channel_id))
+        .cloned();
+    let (group, command) = match last_sent {
+        Some(last_sent) => last_sent,
+        None => {
+            return Err(CommandError::NoState(
+                "No sauce command has been sent in this channel yet".to_string(),
+            )
+            .into())
+        }
+    };
 
-use lazy_static::lazy_static;
+    // The last command may be someone else's, so it gets the checks its own
+    // command would have had.
+    let config = current_config(ctx).await;
+    let command_name = resent_command_name(&group, &command, &config.sauce_keyword)?;
+    if !is_owner(ctx, msg.author.id).await {
+        if group == "raw" {
+            return Err(
+                CommandError::Invalid("Only owners can resend a raw command".to_string()).into(),
+            );
+        }
+        let access = check_access(
+            ctx,
+            &command_name,
+            msg.author.id,
+            msg.channel_id,
+            msg.guild_id,
+            async { msg.member(ctx).await.ok() },
+        )
+        .await;
+        if let Err(why) = access {
+            reply(ctx, msg, why).await?;
+            return Ok(());
+        }
+    }
 
-use nom::{
-    bytes::complete::{tag, take_until1},
-    IResult,
-};
+    send_command(ctx, msg, &group, command).await?;
 
-lazy_static! {
-    static ref LAST_LC: RwLock<String> = RwLock::new(String::default());
-    static ref LAST_SRIRACHA_EMBED_MESSAGE: RwLock<Option<Message>> = RwLock::new(None);
-    static ref BOTS: HashMap<&'static str, u64> = HashMap::from([
-        ("sriracha", 607661949194469376),
-        ("ohsheet", 640402425395675178),
-        ("lc", 661826254215053324),
-        ("fort checker", 1014282115086565486)
-    ]);
+    Ok(())
 }
 
-fn is_sriracha_bot(user: &User) -> bool {
-    vec![BOTS.get("sriracha").unwrap(), BOTS.get("ohsheet").unwrap()].contains(&user.id.as_u64())
-}
+/// The command whose checks `*again` applies before resending `command`, sent
+/// for `group`. Moves and deletes are refused: the ids after them have
+/// shifted, so sending one again would hit a different item.
+fn resent_command_name(group: &str, command: &str, keyword: &str) -> Result<String, CommandError> {
+    let action = command
+        .strip_prefix(keyword)
+        .and_then(|rest| rest.split_whitespace().next());
+    if let Some(action @ ("move" | "delete")) = action {
+        return Err(CommandError::Invalid(format!(
+            "Not resending `{command}`, ids shift after a {action} so repeat the {action} itself"
+        )));
+    }
 
-fn is_lc_bot(user: &User) -> bool {
-    vec![
-        BOTS.get("ohsheet").unwrap(),
-        BOTS.get("lc").unwrap(),
-        BOTS.get("fort checker").unwrap(),
-    ]
-    .contains(&user.id.as_u64())
+    Ok(match group {
+        "author" => "sauceauthor".to_string(),
+        "raw" => "raw".to_string(),
+        group => format!("{group}_list"),
+    })
 }
 
-pub struct ShardManagerContainer;
+#[cfg(test)]
+mod again_tests {
+    use super::*;
 
-impl TypeMapKey for ShardManagerContainer {
-    type Value = Arc<Mutex<ShardManager>>;
+    #[test]
+    fn checks_a_resend_as_the_command_that_sent_it() {
+        let name = |group, command| resent_command_name(group, command, "sauce").ok();
+        assert_eq!(name("lc", "sauce lc 3#5").as_deref(), Some("lc_list"));
+        assert_eq!(name("qc", "sauce 6#2").as_deref(), Some("qc_list"));
+        assert_eq!(
+            name("author", "sauce -qa Someone").as_deref(),
+            Some("sauceauthor")
+        );
+        assert_eq!(name("raw", "sauce list lc").as_deref(), Some("raw"));
+    }
+
+    #[test]
+    fn refuses_to_resend_moves_and_deletes() {
+        for command in ["sauce move 3#5 4", "sauce move C#5 C#2", "sauce delete 2#1"] {
+            assert!(
+                resent_command_name("lc", command, "sauce").is_err(),
+                "{command}"
+            );
+        }
+    }
 }
 
-struct Handler;
+#[command]
+async fn cancel(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let pending = tracked_state(ctx)
+        .await
+        .pending_deletes
+        .lock()
+        .await
+        .remove(&(msg.channel_id, msg.author.id));
 
-fn author_get(input: &str) -> IResult<&str, &str> {
-    let (input, _) = tag("Looking up ")(input)?;
-    let (input, _) = take_until1(" by ")(input)?;
-    let (input, _) = tag(" by ")(input)?;
-    let (input, author) = take_until1(".")(input)?;
+    // `confirm_delete` replies "Delete cancelled" once it sees this.
+    if pending.is_none_or(|pending| pending.send(()).is_err()) {
+        return Err(CommandError::NoState(
+            "You have no pending delete in this channel".to_string(),
+        )
+        .into());
+    }
 
-    Ok((input, author))
+    Ok(())
 }
 
-#[async_trait]
-impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
-        info!("Connected as {}", ready.user.name);
-    }
+#[command]
+#[aliases("use")]
+async fn use_stage(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let active_stages = {
+        let data = ctx.data.read().await;
+        data.get::<ActiveStages>()
+            .expect("Expected ActiveStages in TypeMap")
+            .clone()
+    };
 
-    async fn message(&self, ctx: Context, msg: Message) {
-        if is_sriracha_bot(&msg.author) {
-            if msg.content.starts_with(".lc") {
-                let mut last_lc = LAST_LC.write().await;
-                *last_lc = msg.content.clone();
-            } else if msg.embeds.first().is_some() {
-                let message_id = msg.id;
-                {
-                    let mut last_sriracha_embed_message = LAST_SRIRACHA_EMBED_MESSAGE.write().await;
-                    *last_sriracha_embed_message = Some(msg);
-                }
-                info!("Last sriracha embed message: {}", message_id);
+    let response = match args.single::<String>().ok().map(|arg| arg.to_lowercase()) {
+        Some(arg) if arg == "none" => {
+            active_stages.write().await.remove(&msg.channel_id);
+            "Cleared the active stage in this channel".to_string()
+        }
+        Some(arg) => match STAGES.into_iter().find(|stage| *stage == arg) {
+            Some(stage) => {
+                active_stages.write().await.insert(msg.channel_id, stage);
+                format!("Bare stage commands now go to {stage} in this channel")
             }
-        } else if is_lc_bot(&msg.author) && msg.content.starts_with("Looking up") {
-            match author_get(&msg.content) {
-                Ok((_, author)) => {
-                    sleep(Duration::from_secs(3));
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, format!("sauce -qa {author}"))
-                        .await;
-                }
-                Err(_) => {
-                    let _ = msg.channel_id.say(&ctx.http, "Could not find author").await;
-                }
+            None => {
+                return Err(CommandError::Invalid(format!(
+                    "Unknown stage '{arg}', expected one of {} or `none`",
+                    STAGES.join(", ")
+                ))
+                .into())
             }
+        },
+        None => match active_stages.read().await.get(&msg.channel_id) {
+            Some(stage) => format!("The active stage in this channel is {stage}"),
+            None => "No active stage is set in this channel".to_string(),
+        },
+    };
+    reply(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+async fn ack(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let ack_channels = {
+        let data = ctx.data.read().await;
+        data.get::<AckChannels>()
+            .expect("Expected AckChannels in TypeMap")
+            .clone()
+    };
+
+    let response = match args.single::<String>().ok().as_deref() {
+        Some("on") => {
+            ack_channels.write().await.insert(msg.channel_id);
+            "Acknowledgements are now on in this channel"
         }
-    }
+        Some("off") => {
+            ack_channels.write().await.remove(&msg.channel_id);
+            "Acknowledgements are now off in this channel"
+        }
+        Some(_) => "Expected `on` or `off`",
+        None if ack_channels.read().await.contains(&msg.channel_id) => {
+            "Acknowledgements are on in this channel"
+        }
+        None => "Acknowledgements are off in this channel",
+    };
+    reply(ctx, msg, response).await?;
+
+    Ok(())
 }
 
-#[group]
-#[commands(en, jp)]
-struct General;
+/// Parses `KEY=value` lines from a `.env` file, skipping blanks and `#`
+/// comments and unwrapping quoted values.
+fn parse_env_file(contents: &str) -> EnvVars {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .into_iter()
+                .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
 
-#[group]
-#[prefix = "lc"]
-#[commands(lc_list, lc_move, lc_delete, lc_retry)]
-struct Lc;
+/// The current `.env` with the variables set outside it on top, matching
+/// the precedence `dotenv::dotenv` gave at startup.
+fn reload_env_vars(startup: &StartupEnv) -> Result<EnvVars, String> {
+    let path = startup.env_file.display();
+    let contents = std::fs::read_to_string(&startup.env_file)
+        .map_err(|why| format!("Could not read {path}: {why}"))?;
+    let mut vars = parse_env_file(&contents);
+    vars.extend(startup.process_env.clone());
 
-#[group]
-#[prefix = "st"]
-#[commands(st_list, st_move, st_delete)]
-struct St;
+    Ok(vars)
+}
 
-#[group]
-#[prefix = "qc"]
-#[commands(qc_list, qc_move, qc_delete)]
-struct Qc;
+#[cfg(test)]
+mod env_file_tests {
+    use super::*;
 
-#[tokio::main]
-async fn main() {
-    dotenv::dotenv().expect("Failed to load .env file");
-    tracing_subscriber::fmt::init();
+    #[test]
+    fn parses_env_lines() {
+        let vars = parse_env_file(
+            "# comment\n\nMAX_ID=500\nexport SAUCE_KEYWORD = \"sauce\"\nLIST_ALL_COMMAND='{stage}'\nbroken\n",
+        );
+        assert_eq!(vars.len(), 3);
+        assert_eq!(vars["MAX_ID"], "500");
+        assert_eq!(vars["SAUCE_KEYWORD"], "sauce");
+        assert_eq!(vars["LIST_ALL_COMMAND"], "{stage}");
+    }
 
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in environment");
-    let http = Http::new(&token);
+    #[test]
+    fn keeps_the_process_environment_over_the_file() {
+        let path = env::temp_dir().join(format!("lc-streamliner-env-{}", std::process::id()));
+        std::fs::write(&path, "MAX_ID=500\nSTATUS_ID=2\n").unwrap();
+        let startup = StartupEnv {
+            env_file: path.clone(),
+            process_env: EnvVars::from([("MAX_ID".to_string(), "900".to_string())]),
+        };
+        let vars = reload_env_vars(&startup).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-    let (owners, _bot_id) = match http.get_current_application_info().await {
-        Ok(info) => {
-            let mut owners = HashSet::new();
-            owners.insert(info.owner.id);
+        assert_eq!(vars["MAX_ID"], "900");
+        assert_eq!(vars["STATUS_ID"], "2");
+        let config = Config::from_file(ConfigFile::default(), &vars).unwrap();
+        assert_eq!(config.max_id, 900);
+        assert_eq!(config.status_id, 2);
+    }
+}
 
-            (owners, info.id)
+#[command]
+#[owners_only]
+async fn reload(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let startup = {
+        let data = ctx.data.read().await;
+        data.get::<StartupEnv>()
+            .expect("Expected StartupEnv in TypeMap")
+            .clone()
+    };
+    let new_config = match reload_env_vars(&startup).and_then(|vars| Config::from_env(&vars)) {
+        Ok(new_config) => new_config,
+        Err(why) => {
+            reply(ctx, msg, format!("Could not reload config: {why}")).await?;
+            return Ok(());
         }
-        Err(why) => panic!("Could not access app info: {:?}", why),
     };
 
-    let framework = StandardFramework::new()
-        .configure(|c| c.owners(owners).prefix("*"))
-        .group(&GENERAL_GROUP)
-        .group(&LC_GROUP)
-        .group(&ST_GROUP)
-        .group(&QC_GROUP);
+    let config = config_handle(ctx).await;
+    let changes = {
+        let mut config = config.write().await;
+        let changes = config.changes(&new_config);
+        *config = Arc::new(new_config);
+        changes
+    };
+    info!("Reloaded config with {} change(s)", changes.len());
 
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::GUILD_MESSAGE_REACTIONS;
-    let mut client = Client::builder(&token, intents)
-        .framework(framework)
-        .event_handler(Handler)
-        .await
-        .expect("Error creating client");
+    let response = if changes.is_empty() {
+        "Reloaded config, nothing changed".to_string()
+    } else {
+        format!("Reloaded config:\n{}", changes.join("\n"))
+    };
+    reply(ctx, msg, response).await?;
 
-    {
-        let mut data = client.data.write().await;
-        data.insert::<ShardManagerContainer>(client.shard_manager.clone());
-    }
+    Ok(())
+}
 
-    let shard_manager = client.shard_manager.clone();
+#[command]
+async fn target(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let message_id: u64 = match args.single::<String>() {
+        Ok(input) => parse_number(&input)?,
+        Err(_) => {
+            return Err(
+                CommandError::MissingArg("Expected the id of a message in this channel").into(),
+            )
+        }
+    };
 
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("could not register ctrl+c handler");
-        shard_manager.lock().await.shutdown_all().await;
-    });
+    let target_message = match msg.channel_id.message(&ctx.http, message_id).await {
+        Ok(target_message) => target_message,
+        Err(_) => {
+            reply(ctx, msg, format!("Could not find message {message_id}")).await?;
+            return Ok(());
+        }
+    };
 
-    if let Err(why) = client.start().await {
-        error!("Client error: {:?}", why);
+    if target_message.embeds.is_empty() {
+        reply(ctx, msg, format!("Message {message_id} has no embed")).await?;
+        return Ok(());
     }
-}
 
-fn get_id(mut args: Args) -> Result<u32, ArgError<ParseIntError>> {
-    if args.is_empty() {
-        Ok(1)
-    } else {
-        args.single::<u32>()
-    }
-}
+    let tracked = tracked_state(ctx).await;
+    tracked.push_embed(target_message).await;
+    info!("Last sriracha embed message set to: {}", message_id);
+    save_state(&tracked).await;
 
-#[command]
-#[aliases("")]
-async fn lc_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce lc 3#{id}"))
-        .await?;
+    reply(ctx, msg, format!("Now targeting message {message_id}")).await?;
 
     Ok(())
 }
 
 #[command]
-#[aliases("move")]
-async fn lc_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce move 3#{id} 4"))
+async fn rescan(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let messages = msg
+        .channel_id
+        .messages(&ctx.http, |retriever| {
+            retriever.before(msg.id).limit(RESCAN_LIMIT)
+        })
         .await?;
 
+    let config = current_config(ctx).await;
+    // Newest first, so keep the most recent embeds and push the oldest first.
+    let mut embeds: Vec<_> = messages
+        .into_iter()
+        .filter(|message| config.is_sriracha_bot(&message.author) && !message.embeds.is_empty())
+        .take(EMBED_HISTORY)
+        .collect();
+    let latest = match embeds.first() {
+        Some(latest) => latest.id,
+        None => {
+            reply(ctx, msg, "None found").await?;
+            return Ok(());
+        }
+    };
+
+    let tracked = tracked_state(ctx).await;
+    embeds.reverse();
+    for embed in embeds {
+        tracked.push_embed(embed).await;
+    }
+    info!("Last sriracha embed message set to: {}", latest);
+    save_state(&tracked).await;
+
+    reply(ctx, msg, format!("Now targeting message {latest}")).await?;
+
     Ok(())
 }
 
 #[command]
-#[aliases("del", "delet", "delete")]
-async fn lc_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce delete 3#{id}"))
-        .await?;
+#[sub_commands(author_retry)]
+async fn author(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let tracked = tracked_state(ctx).await;
+    let response = match tracked.last_author.read().await.get(&msg.guild_id) {
+        Some(author) => format!("Last author looked up: {author}"),
+        None => "No author has been looked up yet".to_string(),
+    };
+    reply(ctx, msg, response).await?;
 
     Ok(())
 }
 
 #[command]
 #[aliases("retry")]
-async fn lc_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let retried_message = LAST_LC.read().await.clone();
+async fn author_retry(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let last_author = tracked_state(ctx)
+        .await
+        .last_author
+        .read()
+        .await
+        .get(&msg.guild_id)
+        .cloned();
 
-    msg.channel_id.say(&ctx.http, retried_message).await?;
+    match last_author {
+        Some(authors) => {
+            for author in split_authors(&authors) {
+                send_sauce(ctx, msg, "author", &author_query(author)).await?;
+            }
+        }
+        None => {
+            return Err(
+                CommandError::NoState("No author has been looked up yet".to_string()).into(),
+            );
+        }
+    }
 
     Ok(())
 }
 
 #[command]
-#[aliases("")]
-async fn st_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce 2#{id}"))
-        .await?;
+#[bucket = "sauce"]
+async fn sauceauthor(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = strip_wrapping(args.rest());
+    if author.is_empty() {
+        return Err(
+            CommandError::MissingArg("Expected an author name, e.g. `sauceauthor name`").into(),
+        );
+    }
+
+    tracked_state(ctx)
+        .await
+        .last_author
+        .write()
+        .await
+        .insert(msg.guild_id, author.to_string());
+    send_sauce(ctx, msg, "author", &author_query(author)).await?;
 
     Ok(())
 }
 
-#[command]
-#[aliases("move")]
-async fn st_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce move 2#{id} 3"))
-        .await?;
+/// Reads a message link like `https://discord.com/channels/1/2/3`, or a bare
+/// message id in `channel_id`. Links have to point into `guild_id`, the guild
+/// the command was used in, or `@me` outside of one.
+fn parse_message_ref(
+    input: &str,
+    guild_id: GuildKey,
+    channel_id: ChannelId,
+) -> Result<(ChannelId, u64), CommandError> {
+    let input = input.trim_matches(|c| c == '<' || c == '>');
+    match input.split_once("/channels/") {
+        Some((_, path)) => {
+            let (guild, channel, message) = match path.split('/').collect::<Vec<_>>()[..] {
+                [guild, channel, message] => (guild, channel, message),
+                _ => {
+                    return Err(CommandError::Invalid(format!(
+                        "Expected a message link, got '{input}'"
+                    )))
+                }
+            };
+            let same_guild = match guild_id {
+                Some(guild_id) => guild == guild_id.to_string(),
+                None => guild == "@me",
+            };
+            if !same_guild {
+                return Err(CommandError::Invalid(
+                    "Expected a link to a message in this server".to_string(),
+                ));
+            }
+            Ok((ChannelId(parse_number(channel)?), parse_number(message)?))
+        }
+        None => Ok((channel_id, parse_number(input)?)),
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod message_ref_tests {
+    use super::*;
+
+    #[test]
+    fn reads_links_into_this_guild() {
+        let guild_id = Some(GuildId(1));
+        assert_eq!(
+            parse_message_ref("https://discord.com/channels/1/2/3", guild_id, ChannelId(9)).ok(),
+            Some((ChannelId(2), 3))
+        );
+        assert_eq!(
+            parse_message_ref(
+                "<https://discord.com/channels/1/2/3>",
+                guild_id,
+                ChannelId(9)
+            )
+            .ok(),
+            Some((ChannelId(2), 3))
+        );
+        assert_eq!(
+            parse_message_ref("3", guild_id, ChannelId(9)).ok(),
+            Some((ChannelId(9), 3))
+        );
+        assert_eq!(
+            parse_message_ref("https://discord.com/channels/@me/2/3", None, ChannelId(9)).ok(),
+            Some((ChannelId(2), 3))
+        );
+    }
+
+    #[test]
+    fn rejects_links_into_other_guilds() {
+        for (input, guild_id) in [
+            ("https://discord.com/channels/4/2/3", Some(GuildId(1))),
+            ("https://discord.com/channels/@me/2/3", Some(GuildId(1))),
+            ("https://discord.com/channels/1/2/3", None),
+        ] {
+            assert!(
+                parse_message_ref(input, guild_id, ChannelId(9)).is_err(),
+                "{input}"
+            );
+        }
+    }
 }
 
 #[command]
-#[aliases("del", "delet", "delete")]
-async fn st_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce delete 2#{id}"))
-        .await?;
+#[bucket = "sauce"]
+async fn lookup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input = match args.single::<String>() {
+        Ok(input) => input,
+        Err(_) => {
+            return Err(CommandError::MissingArg(
+                "Expected a message link or id, e.g. `lookup 1234`",
+            )
+            .into())
+        }
+    };
+    let (channel_id, message_id) = parse_message_ref(&input, msg.guild_id, msg.channel_id)?;
+    // The link's guild is just text, so check the channel really is in it.
+    if channel_id != msg.channel_id {
+        let channel_guild = match channel_id.to_channel(ctx).await {
+            Ok(Channel::Guild(channel)) => Some(channel.guild_id),
+            _ => None,
+        };
+        if channel_guild.is_none() || channel_guild != msg.guild_id {
+            reply(ctx, msg, format!("Could not find message {message_id}")).await?;
+            return Ok(());
+        }
+    }
+
+    let looked_up = match channel_id.message(&ctx.http, message_id).await {
+        Ok(looked_up) => looked_up,
+        Err(_) => {
+            reply(ctx, msg, format!("Could not find message {message_id}")).await?;
+            return Ok(());
+        }
+    };
+    let author = match author_get(&looked_up.content) {
+        Ok((_, author)) => author,
+        Err(_) => {
+            reply(
+                ctx,
+                msg,
+                format!("Could not find the author in {:?}", looked_up.content),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    reply(ctx, msg, format!("Found author: {author}")).await?;
+    tracked_state(ctx)
+        .await
+        .last_author
+        .write()
+        .await
+        .insert(msg.guild_id, author.to_string());
+    for author in split_authors(author) {
+        send_sauce(ctx, msg, "author", &author_query(author)).await?;
+    }
 
     Ok(())
 }
 
 #[command]
-#[aliases("")]
-async fn qc_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce 1#{id}"))
-        .await?;
+#[bucket = "sauce"]
+async fn reparse(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let tracked = tracked_state(ctx).await;
+    let corrected = strip_wrapping(args.rest());
+
+    let author = if !corrected.is_empty() {
+        if !is_owner(ctx, msg.author.id).await {
+            reply(ctx, msg, "Only the bot owner can supply the author").await?;
+            return Ok(());
+        }
+
+        corrected.to_string()
+    } else {
+        let content = tracked
+            .failed_lookups
+            .read()
+            .await
+            .get(&msg.channel_id)
+            .cloned();
+        let content = match content {
+            Some(content) => content,
+            None => {
+                return Err(
+                    CommandError::NoState("No failed author lookup to reparse".to_string()).into(),
+                )
+            }
+        };
+
+        match author_get(&content) {
+            Ok((_, author)) => author.to_string(),
+            Err(_) => {
+                reply(
+                    ctx,
+                    msg,
+                    format!("Still could not find the author in {content:?}"),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    tracked.failed_lookups.write().await.remove(&msg.channel_id);
+    tracked
+        .last_author
+        .write()
+        .await
+        .insert(msg.guild_id, author.clone());
+    for author in split_authors(&author) {
+        send_sauce(ctx, msg, "author", &author_query(author)).await?;
+    }
 
     Ok(())
 }
 
-#[command]
-#[aliases("move")]
-async fn qc_move(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce move 1#{id} 2"))
-        .await?;
+/// Picks the embed flag commands react to: the message being replied to, or
+/// the channel's `index`th most recent sriracha embed (1 = latest) when the
+/// command isn't a reply.
+async fn embed_target(
+    ctx: &Context,
+    msg: &Message,
+    index: usize,
+) -> serenity::Result<Option<Message>> {
+    if let Some(referenced) = &msg.referenced_message {
+        if referenced.embeds.is_empty() {
+            reply(ctx, msg, "The message you replied to has no embed").await?;
+            return Ok(None);
+        }
+
+        return Ok(Some((**referenced).clone()));
+    }
+
+    let tracked = tracked_state(ctx).await;
+    let last_sriracha_embed_messages = tracked.last_sriracha_embed_messages.read().await;
+    let embeds = match last_sriracha_embed_messages.get(&msg.channel_id) {
+        Some(embeds) if !embeds.is_empty() => embeds,
+        _ => {
+            drop(last_sriracha_embed_messages);
+            reply(ctx, msg, NO_EMBED_TRACKED).await?;
+            return Ok(None);
+        }
+    };
+
+    match index.checked_sub(1).and_then(|index| embeds.get(index)) {
+        Some(embed) => Ok(Some(embed.clone())),
+        None => {
+            let count = embeds.len();
+            drop(last_sriracha_embed_messages);
+            reply(
+                ctx,
+                msg,
+                format!("Expected an embed number from 1 to {count}, got {index}"),
+            )
+            .await?;
+            Ok(None)
+        }
+    }
+}
+
+fn embed_index(args: &mut Args) -> Result<usize, CommandError> {
+    match args.single::<String>() {
+        Ok(input) => parse_number(&input),
+        Err(_) => Ok(1),
+    }
+}
+
+async fn language_reaction(
+    ctx: &Context,
+    msg: &Message,
+    code: &str,
+    flag: &ReactionType,
+) -> Result<ReactionType, ReactionConversionError> {
+    let config = current_config(ctx).await;
+    let emoji = match config.language_emojis.get(code) {
+        Some(emoji) => emoji,
+        None => return Ok(flag.clone()),
+    };
+
+    if emoji.starts_with('<') {
+        return ReactionType::from_str(emoji);
+    }
+
+    if let Some(guild_id) = msg.guild_id {
+        match guild_id.emojis(&ctx.http).await {
+            Ok(emojis) => {
+                if let Some(found) = emojis.into_iter().find(|found| found.name == *emoji) {
+                    return Ok(found.into());
+                }
+            }
+            Err(why) => warn!("Could not fetch the guild emojis: {:?}", why),
+        }
+    }
+
+    warn!(
+        "Could not find the {} emoji '{}', falling back to the flag",
+        code, emoji
+    );
+    Ok(flag.clone())
+}
+
+async fn react_with_language(
+    ctx: &Context,
+    msg: &Message,
+    code: &str,
+    index: usize,
+) -> CommandResult {
+    let config = current_config(ctx).await;
+    let flag = match config.languages.get(code) {
+        Some(flag) => flag,
+        None => {
+            let supported = config.language_codes().join(", ");
+            reply(
+                ctx,
+                msg,
+                format!("Unknown language '{code}', supported languages: {supported}"),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let reaction = match language_reaction(ctx, msg, code, flag).await {
+        Ok(reaction) => reaction,
+        Err(why) => {
+            error!("Could not build the {} reaction: {:?}", code, why);
+            reply(
+                ctx,
+                msg,
+                format!("Could not build the reaction for '{code}'"),
+            )
+            .await?;
+            return Err(why.into());
+        }
+    };
+
+    if let Some(real_message) = embed_target(ctx, msg, index).await? {
+        let tracked = tracked_state(ctx).await;
+        let key = (real_message.id, code.to_string());
+        let applied = tracked
+            .with_reaction_lock(real_message.id, async {
+                {
+                    let mut language_reactions = tracked.language_reactions.write().await;
+                    language_reactions.retain(|_, at| at.elapsed() < LANGUAGE_COOLDOWN);
+                    if language_reactions.contains_key(&key) {
+                        return Ok(false);
+                    }
+                }
+
+                if let Err(why) = real_message
+                    .delete_reaction_emoji(ctx, reaction.clone())
+                    .await
+                {
+                    // Usually the flag just wasn't there yet.
+                    info!("Could not remove the {} reaction: {:?}", code, why);
+                }
+                real_message.react(ctx, reaction).await?;
+                tracked
+                    .language_reactions
+                    .write()
+                    .await
+                    .insert(key.clone(), Instant::now());
+
+                Ok::<_, serenity::Error>(true)
+            })
+            .await?;
+
+        if !applied {
+            reply(ctx, msg, format!("Already reacted with '{code}' just now")).await?;
+            return Ok(());
+        }
+        metrics(ctx)
+            .await
+            .reactions_applied
+            .fetch_add(1, Ordering::Relaxed);
+    }
 
     Ok(())
 }
 
-#[command]
-#[aliases("del", "delet", "delete")]
-async fn qc_delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let id = get_id(args)?;
-    msg.channel_id
-        .say(&ctx.http, format!("sauce delete 1#{id}"))
+fn same_emoji(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (ReactionType::Custom { id: a, .. }, ReactionType::Custom { id: b, .. }) => a == b,
+        _ => a == b,
+    }
+}
+
+async fn clear_languages(ctx: &Context, msg: &Message) -> CommandResult {
+    let tracked = match embed_target(ctx, msg, 1).await? {
+        Some(tracked) => tracked,
+        None => return Ok(()),
+    };
+    let state = tracked_state(ctx).await;
+    let removed = state
+        .with_reaction_lock(tracked.id, async {
+            // The tracked copy's reactions are stale, so look at the message as it is now.
+            let real_message = tracked.channel_id.message(&ctx.http, tracked.id).await?;
+
+            let config = current_config(ctx).await;
+            let mut removed = 0;
+            for code in config.language_codes() {
+                let flag = &config.languages[code];
+                let mut candidates = vec![flag.clone()];
+                if let Ok(reaction) = language_reaction(ctx, msg, code, flag).await {
+                    candidates.push(reaction);
+                }
+
+                for reaction in real_message.reactions.iter().map(|r| &r.reaction_type) {
+                    if candidates
+                        .iter()
+                        .any(|candidate| same_emoji(candidate, reaction))
+                    {
+                        real_message
+                            .delete_reaction_emoji(ctx, reaction.clone())
+                            .await?;
+                        removed += 1;
+                    }
+                }
+            }
+
+            // Let a flag that was just cleared be put straight back.
+            state
+                .language_reactions
+                .write()
+                .await
+                .retain(|(message_id, _), _| *message_id != tracked.id);
+
+            Ok::<_, serenity::Error>(removed)
+        })
         .await?;
 
+    reply(ctx, msg, format!("Removed {removed} language reaction(s)")).await?;
+
     Ok(())
 }
 
 #[command]
-async fn en(ctx: &Context, _msg: &Message, _args: Args) -> CommandResult {
-    let last_sriracha_embed_message = LAST_SRIRACHA_EMBED_MESSAGE.read().await;
-
-    if let Some(real_message) = &*last_sriracha_embed_message {
-        let _ = real_message
-            .delete_reaction_emoji(ctx, ReactionType::from_str("🇺🇸").unwrap())
-            .await;
-        real_message
-            .react(ctx, ReactionType::from_str("🇺🇸").unwrap())
-            .await?;
+async fn lang(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let code = args.single::<String>().unwrap_or_default().to_lowercase();
+    if code == "clear" {
+        return clear_languages(ctx, msg).await;
     }
-    
 
-    Ok(())
+    react_from_args(ctx, msg, args, &code).await
+}
+
+async fn react_from_args(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+    code: &str,
+) -> CommandResult {
+    let index = embed_index(&mut args)?;
+    react_with_language(ctx, msg, code, index).await
 }
 
 #[command]
-async fn jp(ctx: &Context, _msg: &Message, _args: Args) -> CommandResult {
-    let last_sriracha_embed_message = LAST_SRIRACHA_EMBED_MESSAGE.read().await;
+async fn clearlang(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    clear_languages(ctx, msg).await
+}
 
-    if let Some(real_message) = &*last_sriracha_embed_message {
-        let _ = real_message
-            .delete_reaction_emoji(ctx, ReactionType::from_str("🇯🇵").unwrap())
-            .await;
-        real_message
-            .react(ctx, ReactionType::from_str("🇯🇵").unwrap())
-            .await?;
-    }
+#[command]
+async fn en(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    react_from_args(ctx, msg, args, "en").await
+}
 
-    Ok(())
+#[command]
+async fn jp(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    react_from_args(ctx, msg, args, "jp").await
 }